@@ -0,0 +1,388 @@
+use std::{path::PathBuf, time::Duration};
+
+use clap::{Parser, ValueEnum};
+
+use crate::{
+    compare::{CompareMode, TrimMode},
+    groups::Group,
+    report::ReportFormat,
+};
+
+#[derive(Parser, Debug, Clone)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    /// The name of the task to test
+    pub task: String,
+
+    /// The command to run (defaults to the task name, with .exe on Windows)
+    #[arg(short, long)]
+    pub command: Option<String>,
+
+    /// Arguments passed to the program, given after `--`
+    #[arg(last = true, value_name = "ARGS")]
+    pub program_args: Vec<String>,
+
+    /// Log more details, e.g. the exact command run for each test (-vv for even more)
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Run the program in this directory ({task} is replaced by the task name). The tests are
+    /// still looked up relative to the current directory.
+    #[arg(long, value_name = "DIR")]
+    pub workdir: Option<String>,
+
+    /// Set an environment variable for the program (can be given multiple times)
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_env_var)]
+    pub env: Vec<(String, String)>,
+
+    /// A command to build the program with before running the tests, e.g. "g++ -O2 {task}.cpp -o {task}"
+    #[arg(long, value_name = "CMD")]
+    pub compile: Option<String>,
+
+    /// Input filename pattern. Can be given multiple times (or as a comma-separated list)
+    /// together with `--out-pattern` to collect tests from several places.
+    #[arg(
+        short,
+        long,
+        value_delimiter = ',',
+        default_value = "in/{task}{test}.in"
+    )]
+    pub in_pattern: Vec<String>,
+
+    /// Output filename patern, one for each `--in-pattern`
+    #[arg(
+        short,
+        long,
+        value_delimiter = ',',
+        default_value = "out/{task}{test}.out"
+    )]
+    pub out_pattern: Vec<String>,
+
+    /// Timeout for program execution, in seconds or with a unit (e.g. 2.5, 500ms or 1m),
+    /// measured from starting the program until it exits
+    #[arg(short, long, value_parser = parse_duration, default_value = "5")]
+    pub timeout: Duration,
+
+    /// Warn about passing tests which took more than this fraction of their time limit,
+    /// as they may time out on the judge
+    #[arg(long, value_name = "RATIO", default_value_t = 0.8)]
+    pub warn_ratio: f64,
+
+    /// Memory limit for program execution in megabytes (Unix only)
+    #[arg(short, long, value_name = "MB")]
+    pub memory_limit: Option<u64>,
+
+    /// Total memory in megabytes which all tests running in parallel may use together.
+    /// A test is only started when its memory limit fits into what's left of the budget.
+    #[arg(long, value_name = "MB")]
+    pub total_memory: Option<u64>,
+
+    /// Problem metadata file (JSON or YAML) to read the time and memory limits from,
+    /// unless they're given on the command line
+    #[arg(long, value_name = "FILE")]
+    pub problem_meta: Option<PathBuf>,
+
+    /// Instead of test files, generate a test from this input template for each row of --template-params.
+    /// `{{param}}` placeholders are replaced by the row's values.
+    #[arg(long, value_name = "FILE", requires = "template_params")]
+    pub template: Option<PathBuf>,
+
+    /// Parameters for --template (CSV with a header row, or a JSON array of objects if it ends with .json)
+    #[arg(long, value_name = "FILE", requires = "template")]
+    pub template_params: Option<PathBuf>,
+
+    /// Template of the expected output of each generated test
+    #[arg(long, value_name = "FILE", requires = "template")]
+    pub template_out: Option<PathBuf>,
+
+    /// File assigning tags to tests, each line being a test name followed by its tags
+    #[arg(long, default_value = "{task}.tags")]
+    pub tags_file: String,
+
+    /// Only run tests with this tag (can be given multiple times)
+    #[arg(long)]
+    pub tag: Vec<String>,
+
+    /// Only run tests whose name matches this glob pattern, e.g. "9?"
+    #[arg(long, value_name = "PATTERN")]
+    pub filter: Option<glob::Pattern>,
+
+    /// Declare a subtask of the tests whose names match the glob pattern, worth some points which
+    /// are only awarded if all of them pass, e.g. "sub1:sub1_*:30" (can be given multiple times)
+    #[arg(long, value_name = "NAME:PATTERN:POINTS")]
+    pub group: Vec<Group>,
+
+    /// Only run the test with this name, printing its full input, expected and actual output
+    #[arg(long, value_name = "NAME")]
+    pub only: Option<String>,
+
+    /// How many tests can be ran in parallel, or "auto" for the number of available CPUs
+    #[arg(short, long, value_parser = parse_parallelism, default_value = "auto")]
+    pub parallel: usize,
+
+    /// In which order tests are started
+    #[arg(long, value_enum, default_value_t = Schedule::Discovery)]
+    pub schedule: Schedule,
+
+    /// Kill the program if it prints more than this many bytes (on stdout or stderr)
+    #[arg(long, value_name = "BYTES", default_value_t = 64 * 1024 * 1024)]
+    pub output_limit: u64,
+
+    /// Run each test this many times, reporting timing statistics
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub repeat: u32,
+
+    /// How many times a test is retried after a transient failure (see --retry-on-exit)
+    #[arg(long, default_value_t = 0)]
+    pub retries: usize,
+
+    /// Comma-separated exit codes which are considered transient failures and cause the test to be retried
+    #[arg(long, value_delimiter = ',', value_name = "CODES")]
+    pub retry_on_exit: Vec<i32>,
+
+    /// How many times a test which timed out is retried, running alone so that other tests
+    /// don't compete with it for the CPU
+    #[arg(long, alias = "retry", value_name = "N", default_value_t = 0)]
+    pub retry_timeouts: u32,
+
+    /// Where to store state between runs, like the duration of each test
+    #[arg(long, default_value = ".competitest/{task}.json")]
+    pub cache_file: String,
+
+    /// Don't read or write the cache file
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Only run tests whose input or output file changed since the last run (uses the cache file)
+    #[arg(long)]
+    pub since_last_run: bool,
+
+    /// Don't compare outputs, instead pass a test iff the program exits with this code (0 if not given)
+    #[arg(long, value_name = "CODE", num_args = 0..=1, default_missing_value = "0", allow_negative_numbers = true)]
+    pub expect_exit: Option<i32>,
+
+    /// Besides comparing outputs, require the program to exit with this code.
+    /// Can be overridden for single tests by a `.exit` file next to the input file.
+    #[arg(
+        long,
+        value_name = "CODE",
+        allow_negative_numbers = true,
+        conflicts_with = "expect_exit"
+    )]
+    pub expected_exit: Option<i32>,
+
+    /// Program deciding whether the output is correct, for problems with multiple valid answers.
+    /// It's ran as `<checker> <input> <output> <answer>` and must exit with 0 to accept the output.
+    #[arg(long, value_name = "PATH")]
+    pub checker: Option<String>,
+
+    /// Program the tested program interacts with, for interactive problems. It's ran as
+    /// `<interactor> <input> <output> <answer>`, its stdout is connected to the program's stdin
+    /// and vice versa, and it must exit with 0 to accept the interaction.
+    #[arg(long, value_name = "CMD", conflicts_with_all = ["checker", "expect_exit", "accept"])]
+    pub interactor: Option<String>,
+
+    /// How to compare the program's output against the expected output
+    #[arg(long, value_enum, default_value_t = CompareMode::Exact)]
+    pub compare: CompareMode,
+
+    /// Which whitespace to ignore when comparing outputs exactly
+    #[arg(long, value_enum, default_value_t = TrimMode::Both)]
+    pub trim: TrimMode,
+
+    /// Convert CRLF and lone CR line endings to LF in both outputs before comparing them
+    #[arg(long, conflicts_with_all = ["checker", "interactor", "comparator"])]
+    pub normalize_newlines: bool,
+
+    /// Ignore the case of ASCII letters when comparing outputs, e.g. to accept "Yes" for "YES"
+    #[arg(long, conflicts_with_all = ["checker", "interactor", "comparator"])]
+    pub ignore_case: bool,
+
+    /// Compare numbers in the output allowing the running sum of their differences to drift up to this bound
+    #[arg(long, value_name = "BOUND")]
+    pub cumulative_tolerance: Option<f64>,
+
+    /// Compare numbers in the output allowing an absolute or relative error of up to this epsilon
+    #[arg(long, value_name = "EPS", conflicts_with = "cumulative_tolerance")]
+    pub float_tolerance: Option<f64>,
+
+    /// Command deciding whether the output matches the expected output, e.g. `diff -w`. It's ran
+    /// as `<comparator> <expected> <actual>` and must exit with 0 for the outputs to match.
+    #[arg(
+        long,
+        value_name = "CMD",
+        conflicts_with_all = ["checker", "interactor", "cumulative_tolerance", "float_tolerance"]
+    )]
+    pub comparator: Option<String>,
+
+    /// Append a one-line summary of the run to this file (defaults to ~/.competitest-history)
+    #[arg(long, value_name = "FILE", num_args = 0..=1)]
+    pub history: Option<Option<PathBuf>>,
+
+    /// Command which gets the program's output on stdin and must exit with 0 for the test to pass
+    #[arg(long, value_name = "CMD")]
+    pub output_validator: Option<String>,
+
+    /// File with per-test timings to detect performance regressions against
+    #[arg(long, value_name = "FILE")]
+    pub baseline: Option<PathBuf>,
+
+    /// How many percent slower than in the baseline a test has to be to count as a regression
+    #[arg(long, value_name = "PERCENT", default_value_t = 10.0)]
+    pub regression_threshold: f64,
+
+    /// Save the timings of this run as the baseline instead of checking against it
+    #[arg(long, requires = "baseline")]
+    pub update_baseline: bool,
+
+    /// Open the expected and actual output of failed tests in this diff tool (e.g. meld)
+    #[arg(long, value_name = "CMD")]
+    pub diff_tool: Option<String>,
+
+    /// Only open the first failed test in the diff tool
+    #[arg(long, requires = "diff_tool")]
+    pub diff_first_only: bool,
+
+    /// Save the untrimmed output (and stderr) of each failed test to this directory
+    #[arg(long, value_name = "DIR")]
+    pub save_failures: Option<PathBuf>,
+
+    /// Show at most this many hunks of the diff of long failed outputs (0 for no limit)
+    #[arg(long, value_name = "N", default_value_t = 5)]
+    pub diff_hunks: usize,
+
+    /// Run each test twice and fail it if the outputs of both runs differ
+    #[arg(long)]
+    pub check_determinism: bool,
+
+    /// Instead of comparing, save the program's output as the expected output of each test
+    #[arg(long)]
+    pub accept: bool,
+
+    /// Skip tests whose expected output file doesn't exist with a warning, instead of failing
+    /// before running anything
+    #[arg(long)]
+    pub skip_missing_outputs: bool,
+
+    /// Exit with 0 even if some tests failed
+    #[arg(long)]
+    pub allow_fail: bool,
+
+    /// Stop running tests after the first one fails
+    #[arg(long)]
+    pub fail_fast: bool,
+
+    /// Don't show the progress bar. Without a terminal, it's replaced by occasional progress
+    /// lines in the log, which this turns off as well.
+    #[arg(long)]
+    pub no_progress: bool,
+
+    /// After running the tests, re-run them whenever the program (or a --watch-path) changes
+    #[arg(long)]
+    pub watch: bool,
+
+    /// Also watch this file or directory, e.g. the source code or the tests (can be given multiple times)
+    #[arg(long, value_name = "PATH", requires = "watch")]
+    pub watch_path: Vec<PathBuf>,
+
+    /// How to print the final report
+    #[arg(long, value_enum, default_value_t = ReportFormat::Pretty)]
+    pub format: ReportFormat,
+
+    /// Write a JUnit XML report to this file
+    #[arg(long, value_name = "PATH")]
+    pub junit: Option<PathBuf>,
+
+    /// Append the report (with the result of each test) to this file, with a timestamp
+    #[arg(long, value_name = "PATH")]
+    pub report_file: Option<PathBuf>,
+
+    /// Overwrite the report file instead of appending to it
+    #[arg(long, requires = "report_file")]
+    pub overwrite_report: bool,
+
+    /// Stream the result of each test as soon as it finishes, as a line of JSON, to this file
+    /// descriptor (e.g. 3) or file (appended to)
+    #[arg(long, value_name = "FD|PATH")]
+    pub events: Option<String>,
+
+    /// List every test with its verdict, time and memory in a table after the report
+    #[arg(long)]
+    pub table: bool,
+
+    /// How many of the slowest tests to list in the report (0 to disable)
+    #[arg(long, value_name = "N", default_value_t = 5)]
+    pub show_slowest: usize,
+
+    /// Only list the tests which would be ran with their input and expected output files,
+    /// without running anything
+    #[arg(long, visible_alias = "list")]
+    pub dry_run: bool,
+}
+
+impl Args {
+    /// The options for testing the task, with the same defaults as on the command line.
+    /// Change the others by setting their fields. [`run_suite`](crate::run_suite) only uses the
+    /// options about running the tests, while the ones about the outputs of a run (`format`,
+    /// `table`, `junit`, `report_file`, `history`, `save_failures`, `diff_tool`, `baseline`
+    /// and `allow_fail`) are used by [`report_suite`](crate::report_suite).
+    pub fn new(task: impl Into<String>) -> Self {
+        let mut args = Self::parse_from([env!("CARGO_PKG_NAME"), "task"]);
+        args.task = task.into();
+        args
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Schedule {
+    /// In the order the tests were found in
+    Discovery,
+
+    /// Tests which were the fastest in the previous run first (uses the cache file).
+    /// Tests without a recorded duration are started before all others.
+    ShortestFirst,
+}
+
+fn parse_env_var(arg: &str) -> Result<(String, String), String> {
+    arg.split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected KEY=VALUE, got {}", arg))
+}
+
+/// Parses a number of tests to run in parallel, resolving "auto" to the number of available CPUs
+fn parse_parallelism(arg: &str) -> Result<usize, String> {
+    if arg == "auto" {
+        return Ok(std::thread::available_parallelism().map_or(1, |x| x.get()));
+    }
+
+    arg.parse()
+        .map_err(|_| format!("expected a number or auto, got {}", arg))
+}
+
+/// Parses a positive duration in seconds (may be fractional), or with a unit: `ms`, `s` or `m`
+pub(crate) fn parse_duration(arg: &str) -> Result<Duration, String> {
+    let (number, unit) = match arg.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(pos) => arg.split_at(pos),
+        None => (arg, "s"),
+    };
+
+    let seconds = match unit {
+        "ms" => 0.001,
+        "s" => 1.0,
+        "m" => 60.0,
+        _ => return Err(format!("unknown unit {}, expected ms, s or m", unit)),
+    };
+
+    let number: f64 = number.trim().parse().map_err(|_| {
+        format!(
+            "expected a number of seconds, optionally with a unit (e.g. 2, 2.5s or 500ms), got {}",
+            arg
+        )
+    })?;
+
+    if number.is_nan() || number <= 0.0 {
+        return Err(format!("the duration must be positive, got {}", arg));
+    }
+
+    Duration::try_from_secs_f64(number * seconds).map_err(|e| e.to_string())
+}
@@ -0,0 +1,74 @@
+use std::{collections::BTreeMap, path::Path, time::Duration};
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+/// Recorded per-test timings to detect performance regressions against
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Baseline {
+    /// In milliseconds
+    pub timings: BTreeMap<String, f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Regression {
+    pub name: String,
+    pub baseline: Duration,
+    pub time: Duration,
+}
+
+impl Regression {
+    /// How much slower the test got, in percent
+    pub fn slowdown(&self) -> f64 {
+        (self.time.as_secs_f64() / self.baseline.as_secs_f64() - 1.0) * 100.0
+    }
+}
+
+impl Baseline {
+    pub fn new<'a>(timings: impl IntoIterator<Item = (&'a str, Duration)>) -> Self {
+        Self {
+            timings: timings
+                .into_iter()
+                .map(|(name, time)| {
+                    // microsecond precision is plenty
+                    let millis = (time.as_secs_f64() * 1_000_000.0).round() / 1000.0;
+                    (name.to_string(), millis)
+                })
+                .collect(),
+        }
+    }
+
+    pub async fn load(path: &Path) -> Result<Self> {
+        Ok(serde_json::from_slice(&fs::read(path).await?)?)
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        Ok(fs::write(path, serde_json::to_vec_pretty(self)?).await?)
+    }
+
+    /// Returns the tests which took more than `threshold` percent longer than in the baseline.
+    /// Tests missing from the baseline are ignored.
+    pub fn regressions<'a>(
+        &self,
+        timings: impl IntoIterator<Item = (&'a str, Duration)>,
+        threshold: f64,
+    ) -> Vec<Regression> {
+        timings
+            .into_iter()
+            .filter_map(|(name, time)| {
+                let baseline = *self.timings.get(name)?;
+                let baseline = Duration::try_from_secs_f64(baseline / 1000.0).ok()?;
+
+                (!baseline.is_zero()
+                    && time.as_secs_f64() > baseline.as_secs_f64() * (1.0 + threshold / 100.0))
+                    .then(|| Regression {
+                        name: name.to_string(),
+                        baseline,
+                        time,
+                    })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,67 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use color_eyre::Result;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+/// State remembered between runs of the same task
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Cache {
+    /// How long each test took during its last run, in milliseconds
+    #[serde(default)]
+    pub timings: HashMap<String, u64>,
+
+    /// When the last run which ran each test started
+    #[serde(default)]
+    pub last_runs: HashMap<String, SystemTime>,
+}
+
+impl Cache {
+    /// Loads the cache, falling back to an empty one if it's missing or unreadable
+    pub async fn load(path: &Path) -> Self {
+        let contents = match fs::read(path).await {
+            Ok(x) => x,
+            Err(e) => {
+                debug!("Not loading cache from {}: {}", path.display(), e);
+                return Self::default();
+            }
+        };
+
+        serde_json::from_slice(&contents).unwrap_or_else(|e| {
+            debug!("Ignoring invalid cache {}: {}", path.display(), e);
+            Self::default()
+        })
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        Ok(fs::write(path, serde_json::to_vec_pretty(self)?).await?)
+    }
+
+    pub fn timing(&self, test: &str) -> Option<Duration> {
+        self.timings.get(test).copied().map(Duration::from_millis)
+    }
+
+    pub fn set_timing(&mut self, test: &str, time: Duration) {
+        self.timings.insert(
+            test.to_string(),
+            time.as_millis().try_into().unwrap_or(u64::MAX),
+        );
+    }
+
+    pub fn last_run(&self, test: &str) -> Option<SystemTime> {
+        self.last_runs.get(test).copied()
+    }
+
+    pub fn set_last_run(&mut self, test: &str, time: SystemTime) {
+        self.last_runs.insert(test.to_string(), time);
+    }
+}
@@ -0,0 +1,45 @@
+use std::process::{Output, Stdio};
+
+use color_eyre::{
+    eyre::{eyre, ContextCompat},
+    Result,
+};
+use tokio::{io::AsyncWriteExt, process::Command};
+
+/// Builds a command from a string like `checker --strict`, splitting it shell-style
+pub fn parse_command(command: &str) -> Result<Command> {
+    let parts = shlex::split(command).context(format!("Invalid command: {}", command))?;
+    let (program, args) = parts.split_first().ok_or_else(|| eyre!("Empty command"))?;
+
+    let mut command = Command::new(program);
+    command.args(args);
+
+    Ok(command)
+}
+
+/// Runs the command to completion, feeding it the given input on stdin and capturing its output.
+/// The command not reading all of its input is not an error.
+pub async fn run_with_input(mut command: Command, input: &[u8]) -> Result<Output> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let mut stdin = child.stdin.take().context("Failed to take child's stdin")?;
+
+    let (written, output) = tokio::join!(
+        async move {
+            let res = stdin.write_all(input).await;
+            drop(stdin);
+            res
+        },
+        child.wait_with_output()
+    );
+
+    match written {
+        Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => Err(e)?,
+        _ => Ok(output?),
+    }
+}
@@ -0,0 +1,292 @@
+use std::borrow::Cow;
+
+use clap::ValueEnum;
+
+use crate::{slice_trim_ext::SliceTrimExt, Args};
+
+/// How the program's output is compared against the expected output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum CompareMode {
+    /// Byte-for-byte, ignoring whitespace according to `--trim`
+    #[default]
+    Exact,
+
+    /// Whitespace-separated tokens, ignoring the amount and kind of whitespace between them
+    Tokens,
+
+    /// Blocks separated by blank lines may appear in any order
+    UnorderedBlocks,
+
+    /// Line by line, ignoring trailing whitespace of each line and trailing empty lines
+    Lines,
+}
+
+/// Which whitespace is ignored when comparing outputs exactly
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum TrimMode {
+    /// Leading and trailing whitespace of the whole output
+    #[default]
+    Both,
+
+    /// Trailing whitespace of each line (including CR of CRLF line endings), keeping the number
+    /// of lines
+    TrailingLines,
+
+    /// Nothing, the outputs must be identical
+    None,
+}
+
+impl TrimMode {
+    pub fn normalize(self, output: &[u8]) -> Cow<'_, [u8]> {
+        match self {
+            TrimMode::Both => Cow::Borrowed(output.trim()),
+            TrimMode::TrailingLines => {
+                // the last line may or may not be terminated by a newline
+                let output = output.strip_suffix(b"\n").unwrap_or(output);
+
+                Cow::Owned(
+                    output
+                        .split(|&c| c == b'\n')
+                        .map(SliceTrimExt::trim_line_end)
+                        .collect::<Vec<_>>()
+                        .join(&b'\n'),
+                )
+            }
+            TrimMode::None => Cow::Borrowed(output),
+        }
+    }
+}
+
+impl CompareMode {
+    pub fn matches(self, expected: &[u8], actual: &[u8], trim: TrimMode) -> bool {
+        match self {
+            CompareMode::Exact => trim.normalize(expected) == trim.normalize(actual),
+            CompareMode::Tokens => tokens(expected).eq(tokens(actual)),
+            CompareMode::UnorderedBlocks => sorted_blocks(expected) == sorted_blocks(actual),
+            CompareMode::Lines => content_lines(expected) == content_lines(actual),
+        }
+    }
+}
+
+/// Whether the output matches the expected output according to the comparison options
+/// (`--compare`, `--trim`, `--ignore-case`, ...). `--comparator` isn't handled here, as it runs
+/// an external program.
+pub fn compare(expected: &[u8], actual: &[u8], args: &Args) -> bool {
+    let expected = fold_case(expected, args.ignore_case);
+    let actual = fold_case(actual, args.ignore_case);
+
+    if let Some(bound) = args.cumulative_tolerance {
+        return cumulative_tolerance_eq(&expected, &actual, bound);
+    }
+
+    if let Some(eps) = args.float_tolerance {
+        return float_tolerance_eq(&expected, &actual, eps);
+    }
+
+    if args.normalize_newlines {
+        return args.compare.matches(
+            &normalize_newlines(&expected),
+            &normalize_newlines(&actual),
+            args.trim,
+        );
+    }
+
+    args.compare.matches(&expected, &actual, args.trim)
+}
+
+/// Converts CRLF and lone CR line endings to LF
+pub fn normalize_newlines(output: &[u8]) -> Cow<'_, [u8]> {
+    if !output.contains(&b'\r') {
+        return Cow::Borrowed(output);
+    }
+
+    let mut normalized = Vec::with_capacity(output.len());
+    let mut bytes = output.iter().peekable();
+
+    while let Some(&c) = bytes.next() {
+        if c == b'\r' {
+            bytes.next_if_eq(&&b'\n');
+            normalized.push(b'\n');
+        } else {
+            normalized.push(c);
+        }
+    }
+
+    Cow::Owned(normalized)
+}
+
+/// Lowercases the ASCII letters of the output if `ignore_case` is set. This commutes with the
+/// whitespace normalization of every mode, so it can be done first.
+fn fold_case(output: &[u8], ignore_case: bool) -> Cow<'_, [u8]> {
+    if ignore_case {
+        Cow::Owned(output.to_ascii_lowercase())
+    } else {
+        Cow::Borrowed(output)
+    }
+}
+
+/// Splits the output into lines without trailing whitespace (including CR of CRLF line endings),
+/// dropping the empty lines at the end
+fn content_lines(output: &[u8]) -> Vec<&[u8]> {
+    let mut lines: Vec<_> = output
+        .split(|&c| c == b'\n')
+        .map(SliceTrimExt::trim_line_end)
+        .collect();
+
+    while lines.last().is_some_and(|x| x.is_empty()) {
+        lines.pop();
+    }
+
+    lines
+}
+
+/// Splits the output into blocks of consecutive non-blank lines (a blank line consists only of
+/// whitespace), trims each block and sorts them
+fn sorted_blocks(output: &[u8]) -> Vec<&[u8]> {
+    let mut blocks = vec![];
+    let mut start = None;
+    let mut pos = 0;
+
+    for line in output.split_inclusive(|&c| c == b'\n') {
+        if line.trim().is_empty() {
+            if let Some(start) = start.take() {
+                blocks.push(output[start..pos].trim());
+            }
+        } else if start.is_none() {
+            start = Some(pos);
+        }
+
+        pos += line.len();
+    }
+
+    if let Some(start) = start {
+        blocks.push(output[start..].trim());
+    }
+
+    blocks.sort_unstable();
+    blocks
+}
+
+fn tokens(output: &[u8]) -> impl Iterator<Item = &[u8]> {
+    output
+        .split(|c| c.is_ascii_whitespace())
+        .filter(|x| !x.is_empty())
+}
+
+fn parse_number(token: &[u8]) -> Option<f64> {
+    std::str::from_utf8(token).ok()?.parse().ok()
+}
+
+/// Compares the outputs token by token. Non-numeric tokens must match exactly, while numeric
+/// ones are accepted if either their absolute or relative (to the expected number) error is at
+/// most `eps`. The token counts must match.
+pub fn float_tolerance_eq(expected: &[u8], actual: &[u8], eps: f64) -> bool {
+    let mut expected = tokens(expected);
+    let mut actual = tokens(actual);
+
+    loop {
+        match (expected.next(), actual.next()) {
+            (None, None) => return true,
+            // identical tokens match even if they aren't comparable as numbers, like `nan`
+            (Some(e), Some(a)) if e == a => {}
+            (Some(e), Some(a)) => match (parse_number(e), parse_number(a)) {
+                (Some(e), Some(a)) => {
+                    let error = (a - e).abs();
+
+                    // also accepts equal infinities, whose difference is NaN
+                    if a != e && !(error <= eps || error <= eps * e.abs()) {
+                        return false;
+                    }
+                }
+                _ => {
+                    if e != a {
+                        return false;
+                    }
+                }
+            },
+            _ => return false,
+        }
+    }
+}
+
+/// Compares the outputs token by token. Non-numeric tokens must match exactly, while numeric
+/// ones may differ, as long as the absolute value of the running sum of differences
+/// (`actual - expected`) never exceeds `bound` after any token. The token counts must match.
+pub fn cumulative_tolerance_eq(expected: &[u8], actual: &[u8], bound: f64) -> bool {
+    let mut expected = tokens(expected);
+    let mut actual = tokens(actual);
+
+    let mut drift = 0.0;
+
+    loop {
+        match (expected.next(), actual.next()) {
+            (None, None) => return true,
+            // identical tokens don't add to the drift, even if they're `nan`
+            (Some(e), Some(a)) if e == a => {}
+            (Some(e), Some(a)) => match (parse_number(e), parse_number(a)) {
+                (Some(e), Some(a)) => {
+                    drift += a - e;
+
+                    if drift.is_nan() || drift.abs() > bound {
+                        return false;
+                    }
+                }
+                _ => {
+                    if e != a {
+                        return false;
+                    }
+                }
+            },
+            _ => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    fn args(flags: &[&str]) -> Args {
+        Args::parse_from(["competitest", "task"].iter().chain(flags))
+    }
+
+    #[test]
+    fn ignore_case_matches_different_case() {
+        assert!(compare(b"YES\n", b"yes\n", &args(&["--ignore-case"])));
+    }
+
+    #[test]
+    fn different_case_fails_without_ignore_case() {
+        assert!(!compare(b"YES\n", b"yes\n", &args(&[])));
+    }
+
+    #[test]
+    fn ignore_case_composes_with_tokens() {
+        let args = args(&["--ignore-case", "--compare", "tokens"]);
+        assert!(compare(b"YES  1\nno\n", b"yes 1 NO", &args));
+        assert!(!compare(b"YES 1\n", b"yes 2\n", &args));
+    }
+
+    #[test]
+    fn different_case_fails_with_tokens_only() {
+        assert!(!compare(
+            b"YES 1\n",
+            b"yes 1\n",
+            &args(&["--compare", "tokens"])
+        ));
+    }
+
+    #[test]
+    fn float_tolerance_matches_identical_nan() {
+        assert!(float_tolerance_eq(b"nan 1.0\n", b"nan 1.0\n", 1e-6));
+        assert!(!float_tolerance_eq(b"nan 1.0\n", b"1.0 1.0\n", 1e-6));
+    }
+
+    #[test]
+    fn cumulative_tolerance_matches_identical_nan() {
+        assert!(cumulative_tolerance_eq(b"nan 1.0\n", b"nan 1.0\n", 1e-6));
+        assert!(!cumulative_tolerance_eq(b"nan 1.0\n", b"1.0 1.0\n", 1e-6));
+    }
+}
@@ -0,0 +1,86 @@
+use std::path::{Path, PathBuf};
+
+use color_eyre::{eyre::eyre, Result};
+use toml::{Table, Value};
+
+/// The name of the configuration file, looked for in the current directory and its parents
+const CONFIG_FILE: &str = ".competitest.toml";
+
+/// Default options read from a `.competitest.toml`. Its keys are the names of the options
+/// (e.g. `in_pattern` or `in-pattern` for `--in-pattern`), and its values replace the built-in
+/// defaults, so they can still be overridden on the command line.
+#[derive(Debug, Clone)]
+pub struct Config {
+    path: PathBuf,
+    /// The argument IDs and their values
+    values: Vec<(String, Vec<String>)>,
+}
+
+impl Config {
+    /// Finds the closest configuration file, walking up from the current directory
+    pub fn find() -> Result<Option<Self>> {
+        let cwd = std::env::current_dir()?;
+
+        for dir in cwd.ancestors() {
+            let path = dir.join(CONFIG_FILE);
+            if path.is_file() {
+                return Self::load(&path).map(Some);
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let table: Table = toml::from_str(&std::fs::read_to_string(path)?)
+            .map_err(|e| eyre!("Invalid config file {}: {}", path.display(), e))?;
+
+        let values = table
+            .into_iter()
+            .map(|(key, value)| {
+                let values = match value {
+                    Value::Array(values) => values
+                        .into_iter()
+                        .map(|x| to_arg_value(&key, x))
+                        .collect::<Result<_>>()?,
+                    value => vec![to_arg_value(&key, value)?],
+                };
+
+                Ok((key.replace('-', "_"), values))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            values,
+        })
+    }
+
+    /// Makes the values from the file the default values of the command's arguments
+    pub fn apply(&self, mut command: clap::Command) -> Result<clap::Command> {
+        for (id, values) in self.values.iter() {
+            if !command.get_arguments().any(|x| x.get_id() == id) {
+                return Err(eyre!(
+                    "Unknown option {} in config file {}",
+                    id,
+                    self.path.display()
+                ));
+            }
+
+            command = command.mut_arg(id, |x| x.default_values(values.clone()));
+        }
+
+        Ok(command)
+    }
+}
+
+/// Converts a TOML value to how it would be written on the command line
+fn to_arg_value(key: &str, value: Value) -> Result<String> {
+    match value {
+        Value::String(x) => Ok(x),
+        Value::Integer(x) => Ok(x.to_string()),
+        Value::Float(x) => Ok(x.to_string()),
+        Value::Boolean(x) => Ok(x.to_string()),
+        _ => Err(eyre!("Unsupported value of option {} in config file", key)),
+    }
+}
@@ -0,0 +1,147 @@
+use std::{fmt::Write, time::Duration};
+
+use color_eyre::Result;
+use env_logger::fmt::style::{AnsiColor, Style};
+use similar::{ChangeTag, InlineChangeMode, InlineChangeOptions, TextDiff};
+use tokio::fs;
+
+use crate::command::parse_command;
+
+/// Outputs with at most this many lines are diffed in full, without splitting into hunks
+const SHORT_OUTPUT_LINES: usize = 20;
+
+/// How many unchanged lines are shown around each change
+const CONTEXT_LINES: usize = 3;
+
+/// Renders a line diff between the expected and the actual output.
+/// Within changed lines, the differing characters are highlighted.
+/// Long outputs get a unified diff of at most `max_hunks` hunks (0 for no limit).
+pub fn render_diff(expected: &str, actual: &str, max_hunks: usize) -> String {
+    // make sure the last lines compare equal regardless of the trailing newline
+    let expected = format!("{}\n", expected);
+    let actual = format!("{}\n", actual);
+
+    let diff = TextDiff::configure()
+        .timeout(Duration::from_secs(1))
+        .diff_lines(&expected, &actual);
+
+    let short = expected.lines().count().max(actual.lines().count()) <= SHORT_OUTPUT_LINES;
+    let hunks = if short {
+        vec![diff.ops().to_vec()]
+    } else {
+        diff.grouped_ops(CONTEXT_LINES)
+    };
+
+    let mut options = InlineChangeOptions::new();
+    options.mode(InlineChangeMode::Chars);
+
+    let header_style = Style::new().fg_color(Some(AnsiColor::Cyan.into()));
+
+    let mut out = String::new();
+
+    for (i, hunk) in hunks.iter().enumerate() {
+        if max_hunks != 0 && i == max_hunks {
+            let _ = writeln!(
+                out,
+                "{header_style}... {} more hunks{header_style:#}",
+                hunks.len() - i
+            );
+            break;
+        }
+
+        if !short {
+            let (first, last) = (&hunk[0], &hunk[hunk.len() - 1]);
+            let old = first.old_range().start..last.old_range().end;
+            let new = first.new_range().start..last.new_range().end;
+
+            let _ = writeln!(
+                out,
+                "{header_style}@@ -{},{} +{},{} @@{header_style:#}",
+                old.start + 1,
+                old.len(),
+                new.start + 1,
+                new.len()
+            );
+        }
+
+        for op in hunk {
+            for change in diff.iter_inline_changes_with_options(op, options) {
+                let (sign, style) = match change.tag() {
+                    ChangeTag::Delete => ("-", Style::new().fg_color(Some(AnsiColor::Red.into()))),
+                    ChangeTag::Insert => {
+                        ("+", Style::new().fg_color(Some(AnsiColor::Green.into())))
+                    }
+                    ChangeTag::Equal => (" ", Style::new()),
+                };
+
+                let _ = write!(out, "{style}{sign}{style:#}");
+
+                for (emphasized, value) in change.iter_strings_lossy() {
+                    let style = if emphasized { style.invert() } else { style };
+                    let value = value.trim_end_matches(['\n', '\r']);
+
+                    let _ = write!(out, "{style}{value}{style:#}");
+                }
+
+                out.push('\n');
+            }
+        }
+    }
+
+    out.truncate(out.trim_end().len());
+    out
+}
+
+/// Finds the position (1-based line and column) of the first byte in which the outputs differ
+pub fn first_difference(a: &[u8], b: &[u8]) -> (usize, usize) {
+    let pos = a
+        .iter()
+        .zip(b)
+        .position(|(x, y)| x != y)
+        .unwrap_or(a.len().min(b.len()));
+
+    let before = &a[..pos];
+    let line = before.iter().filter(|&&c| c == b'\n').count() + 1;
+    let column = pos
+        - before
+            .iter()
+            .rposition(|&c| c == b'\n')
+            .map_or(0, |x| x + 1)
+        + 1;
+
+    (line, column)
+}
+
+/// How similar the outputs are, from 0 to 1, based on the fraction of matching lines.
+/// The diff is bounded by a deadline, so this stays fast (but may get less exact) on large outputs.
+pub fn similarity(expected: &[u8], actual: &[u8]) -> f64 {
+    let expected = [expected, b"\n"].concat();
+    let actual = [actual, b"\n"].concat();
+
+    TextDiff::configure()
+        .timeout(Duration::from_millis(100))
+        .diff_lines(expected.as_slice(), actual.as_slice())
+        .ratio()
+        .into()
+}
+
+/// Writes both outputs of a test to temporary files and opens them in an external diff tool,
+/// waiting for it to exit. The files are deleted afterwards.
+pub async fn open_diff_tool(tool: &str, test: &str, expected: &[u8], actual: &[u8]) -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let test = test.replace(['/', '\\'], "_");
+
+    let expected_path = dir.path().join(format!("{}.expected", test));
+    let actual_path = dir.path().join(format!("{}.actual", test));
+
+    fs::write(&expected_path, expected).await?;
+    fs::write(&actual_path, actual).await?;
+
+    parse_command(tool)?
+        .arg(&expected_path)
+        .arg(&actual_path)
+        .status()
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use indicatif::{HumanDuration, ProgressState};
+
+use crate::cache::Cache;
+
+/// Estimates the remaining time of a run from the per-test timings of the previous run,
+/// instead of assuming every test takes the same amount of time
+#[derive(Debug, Clone)]
+pub struct HistoricalEta {
+    inner: Arc<Mutex<HistoricalEtaInner>>,
+}
+
+#[derive(Debug)]
+struct HistoricalEtaInner {
+    parallel: usize,
+
+    /// Expected durations of the tests which haven't finished yet
+    remaining: HashMap<String, Duration>,
+
+    last_update: Instant,
+}
+
+impl HistoricalEta {
+    /// Returns `None` if none of the tests have a recorded timing.
+    /// Tests without one are assumed to take the mean of the recorded timings.
+    pub fn new<'a>(
+        cache: &Cache,
+        tests: impl IntoIterator<Item = &'a str>,
+        parallel: usize,
+    ) -> Option<Self> {
+        let tests: Vec<_> = tests.into_iter().collect();
+
+        let known: Vec<_> = tests.iter().filter_map(|x| cache.timing(x)).collect();
+        if known.is_empty() {
+            return None;
+        }
+        let mean = known.iter().sum::<Duration>() / known.len().try_into().ok()?;
+
+        let remaining = tests
+            .iter()
+            .map(|x| (x.to_string(), cache.timing(x).unwrap_or(mean)))
+            .collect();
+
+        Some(Self {
+            inner: Arc::new(Mutex::new(HistoricalEtaInner {
+                parallel: parallel.max(1),
+                remaining,
+                last_update: Instant::now(),
+            })),
+        })
+    }
+
+    pub fn finish(&self, test: &str) {
+        let mut inner = self.inner.lock().unwrap();
+
+        inner.remaining.remove(test);
+        inner.last_update = Instant::now();
+    }
+
+    pub fn estimate(&self) -> Duration {
+        let inner = self.inner.lock().unwrap();
+
+        let workers = inner.parallel.min(inner.remaining.len()).max(1);
+        let remaining = inner.remaining.values().sum::<Duration>() / workers as u32;
+
+        remaining.saturating_sub(inner.last_update.elapsed())
+    }
+
+    /// Renders the estimate in the same format as indicatif's `{eta}`
+    pub fn render(&self, _: &ProgressState, w: &mut dyn Write) {
+        let _ = write!(w, "{:#}", HumanDuration(self.estimate()));
+    }
+}
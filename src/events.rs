@@ -0,0 +1,78 @@
+use std::{fs::File, io::Write, sync::Mutex};
+
+use color_eyre::{eyre::eyre, Result};
+use serde::Serialize;
+
+use crate::report::{Outcome, TestReport};
+
+/// A line of `--events`, written as soon as a test finishes
+#[derive(Debug, Clone, Serialize)]
+struct TestEvent<'a> {
+    name: &'a str,
+    verdict: Outcome,
+    /// `None` for tests which couldn't be ran
+    time_ms: Option<f64>,
+}
+
+/// Where the results of the tests are streamed to as NDJSON, one line per finished test
+#[derive(Debug)]
+pub struct Events {
+    file: Mutex<File>,
+}
+
+impl Events {
+    /// Opens the target, which is either a file descriptor number (on Unix) or a path to append to
+    pub fn open(target: &str) -> Result<Self> {
+        let file = match target.parse::<i32>() {
+            Ok(fd) => open_fd(fd)?,
+            Err(_) => std::fs::OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(target)
+                .map_err(|e| eyre!("Failed to open the events file {}: {}", target, e))?,
+        };
+
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn emit(&self, report: &TestReport) -> Result<()> {
+        let mut line = serde_json::to_vec(&TestEvent {
+            name: &report.name,
+            verdict: report.outcome,
+            time_ms: report.time_ms,
+        })?;
+        line.push(b'\n');
+
+        let mut file = self.file.lock().unwrap();
+        file.write_all(&line)?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
+/// Duplicates the file descriptor, so it's still open for the next run in watch mode
+#[cfg(unix)]
+fn open_fd(fd: i32) -> Result<File> {
+    use std::os::fd::BorrowedFd;
+
+    if fd < 0 {
+        return Err(eyre!("Invalid file descriptor {}", fd));
+    }
+
+    // SAFETY: only borrowed for duplicating it, which fails if it isn't open
+    let borrowed = unsafe { BorrowedFd::borrow_raw(fd) };
+    let owned = borrowed
+        .try_clone_to_owned()
+        .map_err(|e| eyre!("Failed to open file descriptor {} for events: {}", fd, e))?;
+
+    Ok(File::from(owned))
+}
+
+#[cfg(not(unix))]
+fn open_fd(_fd: i32) -> Result<File> {
+    Err(eyre!(
+        "Writing events to a file descriptor is only supported on Unix"
+    ))
+}
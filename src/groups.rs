@@ -0,0 +1,95 @@
+use std::str::FromStr;
+
+use serde::Serialize;
+
+/// The group of tests which don't belong to any declared group, worth no points
+const OTHER_GROUP: &str = "other";
+
+/// A subtask: tests whose names match the pattern, worth some points which are only awarded if
+/// all of them pass
+#[derive(Debug, Clone)]
+pub struct Group {
+    pub name: String,
+    pub pattern: glob::Pattern,
+    pub points: f64,
+}
+
+impl FromStr for Group {
+    type Err = String;
+
+    /// Parses `NAME:PATTERN:POINTS`, e.g. `sub1:sub1_*:30`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected NAME:PATTERN:POINTS, got {}", s);
+
+        let (name, rest) = s.split_once(':').ok_or_else(invalid)?;
+        let (pattern, points) = rest.rsplit_once(':').ok_or_else(invalid)?;
+
+        Ok(Self {
+            name: name.to_string(),
+            pattern: glob::Pattern::new(pattern).map_err(|e| e.to_string())?,
+            points: points
+                .parse()
+                .map_err(|_| format!("invalid number of points: {}", points))?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupScore {
+    pub name: String,
+    pub passed: usize,
+    pub total: usize,
+    /// The points the group is worth
+    pub points: f64,
+    /// `points` if all of its tests passed, otherwise 0
+    pub awarded: f64,
+}
+
+/// Scores the groups, given the name of each test and whether it passed. A test belongs to
+/// the first group whose pattern matches its name, or to the "other" group if there's none.
+pub fn score<'a>(
+    groups: &[Group],
+    tests: impl IntoIterator<Item = (&'a str, bool)>,
+) -> Vec<GroupScore> {
+    let mut scores: Vec<_> = groups
+        .iter()
+        .map(|x| GroupScore {
+            name: x.name.clone(),
+            passed: 0,
+            total: 0,
+            points: x.points,
+            awarded: 0.0,
+        })
+        .chain(std::iter::once(GroupScore {
+            name: OTHER_GROUP.to_string(),
+            passed: 0,
+            total: 0,
+            points: 0.0,
+            awarded: 0.0,
+        }))
+        .collect();
+
+    for (name, passed) in tests {
+        let index = groups
+            .iter()
+            .position(|x| x.pattern.matches(name))
+            .unwrap_or(groups.len());
+
+        scores[index].passed += passed as usize;
+        scores[index].total += 1;
+    }
+
+    for score in scores.iter_mut() {
+        // an empty group is more likely a typo in its pattern than a passed subtask
+        if score.total > 0 && score.passed == score.total {
+            score.awarded = score.points;
+        }
+    }
+
+    // the "other" group is only interesting if there are ungrouped tests
+    if scores.last().is_some_and(|x| x.total == 0) {
+        scores.pop();
+    }
+
+    scores
+}
@@ -0,0 +1,47 @@
+use std::{path::PathBuf, time::Duration};
+
+use chrono::Local;
+use color_eyre::{eyre::ContextCompat, Result};
+use tokio::{fs::OpenOptions, io::AsyncWriteExt};
+
+/// `~/.competitest-history`
+pub fn default_path() -> Result<PathBuf> {
+    let home = std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .context("Couldn't find the home directory for the history file")?;
+
+    Ok(PathBuf::from(home).join(".competitest-history"))
+}
+
+/// Appends a line summarizing a run to the history file, in the format
+/// `<timestamp> <task> pass=<passed>/<total> slowest=<seconds>s`
+pub async fn append(
+    path: Option<PathBuf>,
+    task: &str,
+    passed: usize,
+    total: usize,
+    slowest: Duration,
+) -> Result<()> {
+    let path = match path {
+        Some(x) => x,
+        None => default_path()?,
+    };
+
+    let line = format!(
+        "{} {} pass={}/{} slowest={:.2}s\n",
+        Local::now().format("%Y-%m-%dT%H:%M:%S%:z"),
+        task,
+        passed,
+        total,
+        slowest.as_secs_f64()
+    );
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(line.as_bytes()).await?;
+
+    Ok(())
+}
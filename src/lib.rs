@@ -0,0 +1,32 @@
+//! The test runner behind the `competitest` command, for running tests from other tools.
+//!
+//! Build the options with [`Args::new`] (or parse them with [`clap::Parser`]), then run the tests
+//! with [`run_suite`], which returns the results instead of printing a report. To also print it
+//! and produce the other outputs the command line does, pass them to [`report_suite`].
+
+mod args;
+pub mod baseline;
+mod cache;
+mod command;
+pub mod compare;
+pub mod config;
+pub mod diff;
+mod eta;
+mod events;
+pub mod groups;
+pub mod history;
+pub mod natural_sort;
+pub mod problem_meta;
+pub mod report;
+mod slice_trim_ext;
+pub mod stress;
+mod suite;
+mod summary;
+mod template;
+pub mod tests;
+pub mod watch;
+
+pub use args::{Args, Schedule};
+pub use suite::{run_suite, run_suite_with_progress, SuiteReport};
+pub use summary::{list_tests, report_suite};
+pub use tests::{get_tests, Test, TestResult, TestTimeoutResult};
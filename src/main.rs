@@ -1,190 +1,196 @@
-mod slice_trim_ext;
-mod tests;
-
-use std::{num::ParseIntError, sync::Arc, time::Duration};
+use std::{io::Write, path::PathBuf, process::ExitCode, time::Duration};
 
 use chrono::Local;
-use clap::Parser;
+use clap::{parser::ValueSource, Args as _, CommandFactory, FromArgMatches};
 use color_eyre::Result;
+use competitest::{
+    config::Config,
+    list_tests,
+    problem_meta::ProblemMeta,
+    report_suite, run_suite_with_progress,
+    stress::{self, StressArgs},
+    tests::program,
+    watch::Watcher,
+    Args,
+};
 use env_logger::fmt::style::{AnsiColor, Style};
-use futures::stream::{FuturesUnordered, StreamExt};
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use indicatif::MultiProgress;
 use indicatif_log_bridge::LogWrapper;
-use log::{error, info};
-use std::io::Write;
-use tests::{get_tests, TestTimeoutResult};
-use tokio::sync::{Mutex, Semaphore};
-
-#[derive(Parser, Debug, Clone)]
-#[command(version, about, long_about = None)]
-struct Args {
-    /// The name of the task to test
-    task: String,
-
-    /// The command to run (defaults to the task name, with .exe on Windows)
-    #[arg(short, long)]
-    command: Option<String>,
-
-    /// Input filename pattern
-    #[arg(short, long, default_value = "in/{task}{test}.in")]
-    in_pattern: String,
-
-    /// Output filename patern
-    #[arg(short, long, default_value = "out/{task}{test}.out")]
-    out_pattern: String,
-
-    /// Timeout for program execution
-    #[arg(short, long, value_parser = parse_duration, default_value = "5")]
-    timeout: Duration,
-
-    /// How many tests can be ran in parallel
-    #[arg(short, long, default_value_t = 5)]
-    parallel: usize,
-}
+use log::{error, info, warn, LevelFilter};
+use tokio_util::sync::CancellationToken;
 
-fn parse_duration(arg: &str) -> Result<Duration, ParseIntError> {
-    Ok(Duration::from_secs(arg.parse()?))
-}
+/// How long the tests get to kill their processes after a second Ctrl-C
+const KILL_GRACE_PERIOD: Duration = Duration::from_millis(200);
 
-#[derive(Debug, Clone)]
-struct TestStats {
-    pub pass: Vec<String>,
-    pub fail: Vec<String>,
-    pub timeout: Vec<String>,
+/// What to do, depending on the subcommand
+enum Mode {
+    Test(Box<Args>),
+    Stress(StressArgs),
 }
 
-impl TestStats {
-    pub fn new() -> Self {
-        Self {
-            pass: vec![],
-            fail: vec![],
-            timeout: vec![],
+fn parse_args() -> Result<Mode> {
+    let mut command = Args::command();
+
+    // options from the config file replace the built-in defaults, so the command line wins
+    if let Some(config) = Config::find()? {
+        command = config.apply(command)?;
+    }
+
+    let matches = command
+        .subcommand(StressArgs::augment_args(clap::Command::new("stress")))
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
+        .get_matches();
+
+    if let Some(("stress", matches)) = matches.subcommand() {
+        return Ok(Mode::Stress(StressArgs::from_arg_matches(matches)?));
+    }
+
+    let mut args = Args::from_arg_matches(&matches)?;
+
+    if let Some(path) = &args.problem_meta {
+        let meta = ProblemMeta::load(path)?;
+        let from_cli = |id| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+        if let (Some(time_limit), false) = (meta.time_limit, from_cli("timeout")) {
+            args.timeout = time_limit;
+        }
+        if let (Some(memory_limit), false) = (meta.memory_limit, from_cli("memory_limit")) {
+            args.memory_limit = Some(memory_limit);
         }
     }
+
+    Ok(Mode::Test(Box::new(args)))
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    color_eyre::install()?;
-
-    let logger =
-        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
-            .format(|buf, record| {
-                let subtle = Style::new().fg_color(Some(AnsiColor::BrightBlack.into()));
-                let level_style = buf.default_level_style(record.level());
-
-                writeln!(
-                    buf,
-                    "{subtle}[{subtle:#}{} {level_style}{:<5}{level_style:#}{subtle}]{subtle:#} {}",
-                    Local::now().format("%d-%m-%Y %H:%M:%S"),
-                    record.level(),
-                    record.args()
-                )
-            })
-            .build();
-
-    let args = Args::parse();
-
-    let tests = get_tests(&args)?;
-    let test_count = tests.len();
+async fn main() -> ExitCode {
+    if let Err(e) = color_eyre::install() {
+        eprintln!("Error: {:?}", e);
+        return ExitCode::from(2);
+    }
 
-    let multi = MultiProgress::new();
-    LogWrapper::new(multi.clone(), logger).try_init()?;
+    match run().await {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            ExitCode::from(2)
+        }
+    }
+}
 
-    let progress_bar = multi.add(ProgressBar::new(test_count.try_into()?));
+/// Runs the tests once or, with --watch, whenever something changes
+async fn run() -> Result<bool> {
+    let mode = parse_args()?;
 
-    progress_bar.set_style(
-        ProgressStyle::with_template(
-            "[{elapsed_precise}]▕{wide_bar}▏{pos}/{len} {percent}% ({msg}, {per_sec:!5} tests/s, ETA: {eta})",
-        )
-        .unwrap()
-        .progress_chars("█▉▊▋▌▍▎▏  "),
-    );
-    progress_bar.set_message("0 failed");
+    let mut logger =
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"));
 
-    let progress_bar = Arc::new(progress_bar);
+    if let Mode::Test(args) = &mode {
+        match args.verbose {
+            0 => {}
+            1 => {
+                logger.filter_level(LevelFilter::Debug);
+            }
+            _ => {
+                logger.filter_level(LevelFilter::Trace);
+            }
+        }
+    }
 
-    let failed_tests = Arc::new(Mutex::new(0usize));
+    let logger = logger
+        .format(|buf, record| {
+            let subtle = Style::new().fg_color(Some(AnsiColor::BrightBlack.into()));
+            let level_style = buf.default_level_style(record.level());
+
+            writeln!(
+                buf,
+                "{subtle}[{subtle:#}{} {level_style}{:<5}{level_style:#}{subtle}]{subtle:#} {}",
+                Local::now().format("%d-%m-%Y %H:%M:%S"),
+                record.level(),
+                record.args()
+            )
+        })
+        .build();
 
-    let semaphore = Arc::new(Semaphore::new(args.parallel));
+    let multi = MultiProgress::new();
+    LogWrapper::new(multi.clone(), logger).try_init()?;
 
-    info!(
-        "Loaded {} tests for task {}. Running {} tests in parallel.",
-        test_count, &args.task, &args.parallel
-    );
+    let args = match mode {
+        Mode::Test(args) => *args,
+        Mode::Stress(args) => return stress::stress(&args).await,
+    };
 
-    let tests: FuturesUnordered<_> = tests
-        .into_iter()
-        .map(|test| {
-            let progress_bar = progress_bar.clone();
-            let failed_tests = failed_tests.clone();
-            let semaphore = semaphore.clone();
+    if cfg!(not(unix)) && args.memory_limit.is_some() {
+        warn!("Memory limits are only supported on Unix, ignoring.");
+    }
 
-            let args = args.clone();
+    if args.dry_run {
+        return list_tests(&args);
+    }
 
-            tokio::spawn(async move {
-                let _permit = semaphore.acquire().await.unwrap();
+    if !args.watch {
+        return run_and_report(&args, &multi).await;
+    }
 
-                let name = test.name.clone();
-                let ret = test.run(&args).await;
-                if let Err(e) = &ret {
-                    error!("✖ Test {} - ERROR\n{:?}", name, e);
-                }
+    let mut paths = args.watch_path.clone();
+    if args.compile.is_none() {
+        paths.push(PathBuf::from(program(&args)));
+    }
 
-                let incr_failed_tests = || async {
-                    let mut failed_tests = failed_tests.lock().await;
-                    *failed_tests += 1;
-                    progress_bar.set_message(format!("{} failed", *failed_tests));
-                };
-
-                if let Ok(TestTimeoutResult::Finished(x)) = &ret {
-                    if !x.correct {
-                        incr_failed_tests().await;
-                    }
-                } else {
-                    incr_failed_tests().await;
-                }
+    let mut watcher = Watcher::new(&paths)?;
 
-                progress_bar.inc(1);
-                ret
-            })
-        })
-        .collect();
-
-    let results: Vec<_> = tests
-        .collect::<Vec<_>>()
-        .await
-        .into_iter()
-        .filter_map(|x| x.ok())
-        .filter_map(|x| x.ok())
-        .collect();
-
-    let mut stats = TestStats::new();
-
-    for test in results.iter() {
-        match test {
-            TestTimeoutResult::TimedOut(name) => {
-                stats.timeout.push(name.to_string());
-            }
-            TestTimeoutResult::Finished(res) => {
-                if res.correct {
-                    stats.pass.push(res.name.clone());
-                } else {
-                    stats.fail.push(res.name.clone());
+    loop {
+        // clear the screen
+        print!("\x1B[2J\x1B[H");
+
+        tokio::select! {
+            ret = run_and_report(&args, &multi) => {
+                if let Err(e) = ret {
+                    error!("{:?}", e);
                 }
             }
+            _ = tokio::signal::ctrl_c() => return Ok(true),
         }
-    }
 
-    progress_bar.finish();
+        // changes made while running (e.g. by --compile) don't count
+        watcher.drain();
+        info!("Watching for changes, press Ctrl-C to exit...");
+
+        tokio::select! {
+            _ = watcher.changed() => {}
+            _ = tokio::signal::ctrl_c() => return Ok(true),
+        }
+    }
+}
 
-    println!(
-        "*** TEST REPORT ***\n  TOTAL: {}\n✔ PASS: {}\n✖ FAIL: {}\n✖ TIMEOUT: {}",
-        test_count,
-        stats.pass.len(),
-        stats.fail.len(),
-        stats.timeout.len()
-    );
+/// Runs the tests and prints the report. On Ctrl-C, the running tests are stopped and the
+/// finished ones are reported, while a second Ctrl-C exits immediately.
+async fn run_and_report(args: &Args, multi: &MultiProgress) -> Result<bool> {
+    let stop = CancellationToken::new();
 
-    Ok(())
+    let interrupt = async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return std::future::pending().await;
+        }
+        warn!("Interrupted, stopping the tests... Press Ctrl-C again to exit immediately.");
+        stop.cancel();
+
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    let run = async {
+        let suite = run_suite_with_progress(args, multi, &stop).await?;
+        report_suite(args, &suite).await
+    };
+
+    tokio::select! {
+        ret = run => ret,
+        _ = interrupt => {
+            // the dropped run aborts the tests, give them a moment to kill their processes
+            tokio::time::sleep(KILL_GRACE_PERIOD).await;
+            std::process::exit(130)
+        }
+    }
 }
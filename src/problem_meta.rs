@@ -0,0 +1,84 @@
+use std::{path::Path, time::Duration};
+
+use color_eyre::{eyre::eyre, Result};
+use serde::Deserialize;
+
+/// Limits read from a problem's metadata file (JSON or YAML).
+///
+/// The following shapes are understood:
+/// - `time_limit` (seconds) and `memory_limit` (MB) at the top level
+/// - Polygon's `timeLimit` (milliseconds) and `memoryLimit` (MB), optionally wrapped in `result`
+///   as returned by its API
+/// - DOMjudge/Kattis `problem.yaml`'s `limits.time_limit` (seconds) and `limits.memory` (MB)
+#[derive(Debug, Clone, Default)]
+pub struct ProblemMeta {
+    pub time_limit: Option<Duration>,
+
+    /// In megabytes
+    pub memory_limit: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RawMeta {
+    time_limit: Option<f64>,
+    memory_limit: Option<u64>,
+
+    #[serde(rename = "timeLimit")]
+    polygon_time_limit: Option<u64>,
+    #[serde(rename = "memoryLimit")]
+    polygon_memory_limit: Option<u64>,
+    result: Option<Box<RawMeta>>,
+
+    limits: Option<RawLimits>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RawLimits {
+    time_limit: Option<f64>,
+    memory: Option<u64>,
+}
+
+impl RawMeta {
+    fn into_meta(self) -> Result<ProblemMeta> {
+        let nested = match self.result {
+            Some(x) => x.into_meta()?,
+            None => ProblemMeta::default(),
+        };
+        let limits = self.limits.unwrap_or_default();
+
+        let time_limit = match (
+            self.time_limit.or(limits.time_limit),
+            self.polygon_time_limit,
+        ) {
+            (Some(secs), _) => Some(
+                Duration::try_from_secs_f64(secs)
+                    .map_err(|_| eyre!("Invalid time limit: {}", secs))?,
+            ),
+            (None, Some(millis)) => Some(Duration::from_millis(millis)),
+            (None, None) => nested.time_limit,
+        };
+
+        Ok(ProblemMeta {
+            time_limit,
+            memory_limit: self
+                .memory_limit
+                .or(self.polygon_memory_limit)
+                .or(limits.memory)
+                .or(nested.memory_limit),
+        })
+    }
+}
+
+impl ProblemMeta {
+    /// Parses the file as YAML if its extension is `.yaml`/`.yml`, otherwise as JSON
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read(path)?;
+
+        let raw: RawMeta = match path.extension().and_then(|x| x.to_str()) {
+            Some("yaml" | "yml") => serde_yaml::from_slice(&contents)?,
+            _ => serde_json::from_slice(&contents)?,
+        };
+
+        raw.into_meta()
+    }
+}
@@ -0,0 +1,343 @@
+use std::{fmt::Write, time::Duration};
+
+use clap::ValueEnum;
+use color_eyre::Report;
+use env_logger::fmt::style::{AnsiColor, Style};
+use serde::Serialize;
+
+use crate::{groups::GroupScore, tests::TestTimeoutResult};
+
+/// How many bytes of a failed test's output are included in the report
+const OUTPUT_LIMIT: usize = 1000;
+
+/// How the final report is printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// A human-readable summary
+    #[default]
+    Pretty,
+
+    /// A single JSON object with the results of each test
+    Json,
+
+    /// A Test Anything Protocol stream, e.g. for `prove`
+    Tap,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Pass,
+    Fail,
+    RuntimeError,
+    MemoryLimitExceeded,
+    Timeout,
+    /// The test couldn't be ran, e.g. because its input file is missing
+    Error,
+}
+
+impl Outcome {
+    fn color(self) -> AnsiColor {
+        match self {
+            Outcome::Pass => AnsiColor::Green,
+            Outcome::Timeout => AnsiColor::Yellow,
+            _ => AnsiColor::Red,
+        }
+    }
+
+    /// How the outcome is shown in the logs
+    pub fn label(self) -> &'static str {
+        match self {
+            Outcome::Pass => "PASS",
+            Outcome::Fail => "FAIL",
+            Outcome::RuntimeError => "RUNTIME ERROR",
+            Outcome::MemoryLimitExceeded => "MEMORY LIMIT EXCEEDED",
+            Outcome::Timeout => "TIMEOUT",
+            Outcome::Error => "ERROR",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TestReport {
+    pub name: String,
+    pub outcome: Outcome,
+    /// `None` for tests which couldn't be ran
+    pub time_ms: Option<f64>,
+    /// Peak resident set size of the program, only known on Unix
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peak_memory_kb: Option<u64>,
+    /// The test passed, but close to the time limit (see `--warn-ratio`)
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub slow_pass: bool,
+    /// The test passed, but only after retrying a timeout
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub passed_after_retry: bool,
+
+    /// The (truncated) output of a failed test
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    /// Why the test couldn't be ran
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl TestReport {
+    pub fn from_result(result: &TestTimeoutResult) -> Self {
+        match result {
+            TestTimeoutResult::TimedOut(name, timeout) => Self {
+                name: name.clone(),
+                outcome: Outcome::Timeout,
+                time_ms: Some(millis(*timeout)),
+                peak_memory_kb: None,
+                slow_pass: false,
+                passed_after_retry: false,
+                output: None,
+                error: None,
+            },
+            TestTimeoutResult::Finished(res) => {
+                let outcome = if res.correct {
+                    Outcome::Pass
+                } else if res.memory_limit_exceeded {
+                    Outcome::MemoryLimitExceeded
+                } else if res.runtime_error {
+                    Outcome::RuntimeError
+                } else {
+                    Outcome::Fail
+                };
+
+                let stdout = &res.output.stdout;
+
+                Self {
+                    name: res.name.clone(),
+                    outcome,
+                    time_ms: Some(millis(res.time)),
+                    peak_memory_kb: res.peak_memory.map(|x| x / 1024),
+                    slow_pass: res.slow_pass,
+                    passed_after_retry: res.passed_after_retry,
+                    output: (outcome != Outcome::Pass).then(|| {
+                        String::from_utf8_lossy(&stdout[..stdout.len().min(OUTPUT_LIMIT)])
+                            .into_owned()
+                    }),
+                    error: None,
+                }
+            }
+        }
+    }
+
+    pub fn error(name: &str, error: &Report) -> Self {
+        Self {
+            name: name.to_string(),
+            outcome: Outcome::Error,
+            time_ms: None,
+            peak_memory_kb: None,
+            slow_pass: false,
+            passed_after_retry: false,
+            output: None,
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonReport<'a> {
+    pub task: &'a str,
+    pub total: usize,
+    pub passed: usize,
+    pub tests: &'a [TestReport],
+    /// The score of each group, if any were declared with `--group`
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub groups: &'a [GroupScore],
+}
+
+/// Escapes text for use in XML attributes and elements, dropping characters XML doesn't allow
+fn xml_escape(text: &str) -> String {
+    text.chars()
+        .filter(|&c| matches!(c, '\t' | '\n' | '\r') || c >= ' ')
+        .fold(String::new(), |mut acc, c| {
+            match c {
+                '&' => acc.push_str("&amp;"),
+                '<' => acc.push_str("&lt;"),
+                '>' => acc.push_str("&gt;"),
+                '"' => acc.push_str("&quot;"),
+                '\'' => acc.push_str("&apos;"),
+                c => acc.push(c),
+            }
+            acc
+        })
+}
+
+/// Renders the results as a JUnit XML `<testsuite>`. Wrong answers are reported as failures,
+/// while runtime errors, timeouts and tests which couldn't be ran are reported as errors.
+pub fn junit(task: &str, tests: &[TestReport]) -> String {
+    let count = |outcomes: &[Outcome]| {
+        tests
+            .iter()
+            .filter(|x| outcomes.contains(&x.outcome))
+            .count()
+    };
+    let seconds = |x: &TestReport| x.time_ms.unwrap_or_default() / 1000.0;
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        out,
+        r#"<testsuite name="{}" tests="{}" failures="{}" errors="{}" skipped="0" time="{:.3}">"#,
+        xml_escape(task),
+        tests.len(),
+        count(&[Outcome::Fail]),
+        count(&[
+            Outcome::RuntimeError,
+            Outcome::MemoryLimitExceeded,
+            Outcome::Timeout,
+            Outcome::Error
+        ]),
+        tests.iter().map(seconds).sum::<f64>()
+    );
+
+    for test in tests {
+        let _ = write!(
+            out,
+            r#"  <testcase name="{}" classname="{}" time="{:.3}""#,
+            xml_escape(&test.name),
+            xml_escape(task),
+            seconds(test)
+        );
+
+        let (element, message) = match test.outcome {
+            Outcome::Pass => {
+                out.push_str("/>\n");
+                continue;
+            }
+            Outcome::Fail => ("failure", "Wrong answer"),
+            Outcome::RuntimeError => ("error", "Runtime error"),
+            Outcome::MemoryLimitExceeded => ("error", "Memory limit exceeded"),
+            Outcome::Timeout => ("error", "Timed out"),
+            Outcome::Error => ("error", "Couldn't run the test"),
+        };
+
+        let body = test.error.as_ref().or(test.output.as_ref());
+        let _ = writeln!(
+            out,
+            ">\n    <{element} message=\"{message}\">{}</{element}>\n  </testcase>",
+            xml_escape(body.map_or("", |x| x.as_str()))
+        );
+    }
+
+    out.push_str("</testsuite>\n");
+    out
+}
+
+/// Renders the results as a table with the name, outcome, time and (if known) peak memory of
+/// each test, in the given order
+pub fn table(tests: &[TestReport], color: bool) -> String {
+    let rows: Vec<_> = tests
+        .iter()
+        .map(|x| {
+            [
+                x.name.clone(),
+                x.outcome.label().to_string(),
+                x.time_ms
+                    .map_or("-".to_string(), |x| format!("{:.2} s", x / 1000.0)),
+                x.peak_memory_kb
+                    .map_or("-".to_string(), |x| format!("{:.1} MB", x as f64 / 1024.0)),
+            ]
+        })
+        .collect();
+
+    let header = ["TEST", "VERDICT", "TIME", "MEMORY"];
+    let columns = if tests.iter().any(|x| x.peak_memory_kb.is_some()) {
+        4
+    } else {
+        3
+    };
+    let widths: Vec<_> = (0..columns)
+        .map(|i| {
+            rows.iter()
+                .map(|x| x[i].chars().count())
+                .chain([header[i].len()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "  {:<w0$}  {:<w1$}  {:>w2$}{}",
+        header[0],
+        header[1],
+        header[2],
+        match columns {
+            4 => format!("  {:>w$}", header[3], w = widths[3]),
+            _ => String::new(),
+        },
+        w0 = widths[0],
+        w1 = widths[1],
+        w2 = widths[2],
+    );
+
+    for (test, row) in tests.iter().zip(rows.iter()) {
+        // padded before coloring, so the escape codes don't count towards the width
+        let verdict = format!("{:<w$}", row[1], w = widths[1]);
+        let style = match color {
+            true => Style::new().fg_color(Some(test.outcome.color().into())),
+            false => Style::new(),
+        };
+
+        let _ = writeln!(
+            out,
+            "  {:<w0$}  {style}{verdict}{style:#}  {:>w2$}{}",
+            row[0],
+            row[2],
+            match columns {
+                4 => format!("  {:>w$}", row[3], w = widths[3]),
+                _ => String::new(),
+            },
+            w0 = widths[0],
+            w2 = widths[2],
+        );
+    }
+
+    out
+}
+
+/// Renders the results as a TAP stream: the plan, then an `ok`/`not ok` line for each test, with
+/// the reason of failures as diagnostics
+pub fn tap(tests: &[TestReport]) -> String {
+    let mut out = format!("1..{}\n", tests.len());
+
+    for (i, test) in tests.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "{} {} - {}",
+            if test.outcome == Outcome::Pass {
+                "ok"
+            } else {
+                "not ok"
+            },
+            i + 1,
+            // `#` would start a directive
+            test.name.replace('#', "\\#")
+        );
+
+        match test.outcome {
+            Outcome::Pass => {}
+            Outcome::Timeout => out.push_str("# TIMED OUT\n"),
+            Outcome::Error => {
+                let _ = writeln!(out, "# {}", test.outcome.label());
+                for line in test.error.iter().flat_map(|x| x.lines()) {
+                    let _ = writeln!(out, "#   {}", line);
+                }
+            }
+            outcome => {
+                let _ = writeln!(out, "# {}", outcome.label());
+            }
+        }
+    }
+
+    out
+}
+
+fn millis(time: Duration) -> f64 {
+    (time.as_secs_f64() * 1_000_000.0).round() / 1000.0
+}
@@ -2,6 +2,9 @@
 
 pub trait SliceTrimExt {
     fn trim(&self) -> &Self;
+
+    /// Only trims trailing spaces, tabs and carriage returns, for a single line
+    fn trim_line_end(&self) -> &Self;
 }
 
 impl SliceTrimExt for [u8] {
@@ -24,4 +27,14 @@ impl SliceTrimExt for [u8] {
             &[]
         }
     }
+
+    fn trim_line_end(&self) -> &[u8] {
+        match self
+            .iter()
+            .rposition(|c| !matches!(c, b' ' | b'\t' | b'\r'))
+        {
+            Some(last) => &self[..last + 1],
+            None => &[],
+        }
+    }
 }
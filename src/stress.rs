@@ -0,0 +1,147 @@
+use std::{process::Output, time::Duration};
+
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
+};
+use log::{error, info};
+use tokio::time::timeout;
+
+use crate::{
+    command::{parse_command, run_with_input},
+    compare::{CompareMode, TrimMode},
+    diff::render_diff,
+    slice_trim_ext::SliceTrimExt,
+    tests::describe_exit_status,
+};
+
+/// Looks for a counterexample by comparing a solution against a brute force on generated inputs
+#[derive(clap::Args, Debug, Clone)]
+pub struct StressArgs {
+    /// The generator to run, getting the seed as its last argument and printing an input
+    #[arg(short, long)]
+    generator: String,
+
+    /// The solution to test
+    #[arg(short, long)]
+    solution: String,
+
+    /// The (slow, but correct) solution to compare against
+    #[arg(short, long)]
+    brute: String,
+
+    /// How many inputs to try
+    #[arg(short = 'n', long, default_value_t = 100)]
+    iterations: u64,
+
+    /// The seed of the first iteration, incremented for each one
+    #[arg(long, default_value_t = 1)]
+    seed: u64,
+
+    /// Timeout for each program execution, in seconds or with a unit (e.g. 2.5, 500ms or 1m)
+    #[arg(short, long, value_parser = crate::args::parse_duration, default_value = "5")]
+    timeout: Duration,
+
+    /// How to compare the outputs of both solutions
+    #[arg(long, value_enum, default_value_t = CompareMode::Exact)]
+    compare: CompareMode,
+}
+
+/// Runs a command with the given input, returning `None` if it times out
+async fn run(
+    command: &str,
+    extra_args: &[String],
+    input: &[u8],
+    limit: Duration,
+) -> Result<Option<Output>> {
+    let mut parsed = parse_command(command)?;
+    parsed.args(extra_args);
+
+    timeout(limit, run_with_input(parsed, input))
+        .await
+        .ok()
+        .transpose()
+}
+
+fn describe(output: &Output) -> String {
+    format!(
+        "{}\n(the program {})",
+        String::from_utf8_lossy(output.stdout.trim()),
+        describe_exit_status(&output.status)
+    )
+}
+
+/// Returns whether no counterexample was found
+pub async fn stress(args: &StressArgs) -> Result<bool> {
+    info!(
+        "Stress testing {} against {} on {} inputs.",
+        &args.solution, &args.brute, args.iterations
+    );
+
+    for seed in args.seed..args.seed.saturating_add(args.iterations) {
+        let generated = run(&args.generator, &[seed.to_string()], &[], args.timeout)
+            .await?
+            .ok_or_else(|| eyre!("The generator timed out with seed {}", seed))?;
+        if !generated.status.success() {
+            bail!(
+                "The generator {} with seed {}:\n{}",
+                describe_exit_status(&generated.status),
+                seed,
+                String::from_utf8_lossy(&generated.stderr)
+            );
+        }
+        let input = generated.stdout;
+
+        let brute = run(&args.brute, &[], &input, args.timeout)
+            .await?
+            .ok_or_else(|| eyre!("The brute force timed out with seed {}", seed))?;
+        if !brute.status.success() {
+            bail!(
+                "The brute force {} with seed {}:\n{}",
+                describe_exit_status(&brute.status),
+                seed,
+                String::from_utf8_lossy(&brute.stderr)
+            );
+        }
+
+        let solution = run(&args.solution, &[], &input, args.timeout).await?;
+
+        let correct = solution.as_ref().is_some_and(|x| {
+            x.status.success()
+                && args
+                    .compare
+                    .matches(&brute.stdout, &x.stdout, TrimMode::default())
+        });
+
+        if correct {
+            info!("✔ Seed {} - OK", seed);
+            continue;
+        }
+
+        let details = match &solution {
+            Some(solution) => format!(
+                "Solution:\n{}\nDiff (- brute force, + solution):\n{}",
+                describe(solution),
+                render_diff(
+                    &String::from_utf8_lossy(brute.stdout.trim()),
+                    &String::from_utf8_lossy(solution.stdout.trim()),
+                    0
+                )
+            ),
+            None => "Solution:\n(timed out)".to_string(),
+        };
+
+        error!(
+            "✖ Found a counterexample with seed {}\nInput:\n{}\nBrute force:\n{}\n{}",
+            seed,
+            String::from_utf8_lossy(input.trim()),
+            describe(&brute),
+            details
+        );
+
+        return Ok(false);
+    }
+
+    info!("✔ No counterexample found.");
+    Ok(true)
+}
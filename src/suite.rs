@@ -0,0 +1,377 @@
+use std::{
+    collections::HashMap,
+    fmt::Write as FmtWrite,
+    io::{self, IsTerminal},
+    path::PathBuf,
+    sync::Arc,
+    time::SystemTime,
+};
+
+use color_eyre::{eyre::eyre, Report, Result};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressState, ProgressStyle};
+use log::{error, info, warn};
+use tokio::{
+    sync::{Mutex, Semaphore},
+    task::JoinSet,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    cache::Cache,
+    command::parse_command,
+    eta::HistoricalEta,
+    events::Events,
+    natural_sort::natural_cmp,
+    report::TestReport,
+    tests::{describe_exit_status, get_tests, workdir, TestTimeoutResult},
+    Args, Schedule,
+};
+
+/// The results of a run, sorted by the names of the tests
+#[derive(Debug)]
+pub struct SuiteReport {
+    /// How many tests were found, including the skipped ones
+    pub test_count: usize,
+    pub results: Vec<TestTimeoutResult>,
+    /// The tests which couldn't be ran, and why
+    pub errors: Vec<(String, Report)>,
+    /// The tags of each test
+    pub tags: HashMap<String, Vec<String>>,
+    /// The run was stopped by cancelling its token
+    pub interrupted: bool,
+}
+
+impl SuiteReport {
+    /// The report of each test, including those which couldn't be ran
+    pub fn reports(&self) -> Vec<TestReport> {
+        self.results
+            .iter()
+            .map(TestReport::from_result)
+            .chain(
+                self.errors
+                    .iter()
+                    .map(|(name, e)| TestReport::error(name, e)),
+            )
+            .collect()
+    }
+}
+
+/// Compiles the program (if needed) and runs all tests, without showing the progress.
+/// Cancelling `stop` stops the running tests (killing their processes) and skips the rest.
+///
+/// This only runs the tests, the options about the outputs of a run (like `format`, `junit`,
+/// `history` or `baseline`) are handled by [`report_suite`](crate::report_suite).
+pub async fn run_suite(args: &Args, stop: &CancellationToken) -> Result<SuiteReport> {
+    run_suite_with_progress(
+        args,
+        &MultiProgress::with_draw_target(ProgressDrawTarget::hidden()),
+        stop,
+    )
+    .await
+}
+
+/// Like [`run_suite`], showing the progress bar in `multi`
+pub async fn run_suite_with_progress(
+    args: &Args,
+    multi: &MultiProgress,
+    stop: &CancellationToken,
+) -> Result<SuiteReport> {
+    let cache_path = PathBuf::from(args.cache_file.replace("{task}", &args.task));
+    let mut cache = if args.no_cache {
+        Cache::default()
+    } else {
+        Cache::load(&cache_path).await
+    };
+
+    if let Some(compile) = &args.compile {
+        info!("Compiling task {}...", &args.task);
+
+        let output = parse_command(&compile.replace("{task}", &args.task))?
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            return Err(eyre!(
+                "Compilation failed, the compiler {}:\n{}",
+                describe_exit_status(&output.status),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+    }
+
+    if let Some(dir) = workdir(args).filter(|x| !x.is_dir()) {
+        return Err(eyre!(
+            "The working directory {} doesn't exist",
+            dir.display()
+        ));
+    }
+
+    let run_start = SystemTime::now();
+
+    let mut tests = get_tests(args)?;
+
+    if args.since_last_run {
+        if !cache.last_runs.is_empty() {
+            let total = tests.len();
+            // tests which weren't ran by any previous run (e.g. skipped by --fail-fast) are kept
+            tests.retain(|x| {
+                cache
+                    .last_run(&x.name)
+                    .is_none_or(|last_run| x.modified_since(last_run))
+            });
+
+            info!(
+                "Skipping {} tests which didn't change since the last run.",
+                total - tests.len()
+            );
+        } else {
+            info!("No previous run found, running all tests.");
+        }
+    }
+
+    if args.schedule == Schedule::ShortestFirst {
+        tests.sort_by_key(|x| cache.timing(&x.name).unwrap_or_default());
+    }
+
+    let test_count = tests.len();
+    let parallel = args.parallel.clamp(1, test_count.max(1));
+
+    let tags: HashMap<_, _> = tests
+        .iter()
+        .map(|x| (x.name.clone(), x.tags.clone()))
+        .collect();
+
+    // the progress bar would only fill redirected output with control characters
+    let terminal = io::stderr().is_terminal();
+    let progress_lines = !terminal && !args.no_progress;
+
+    let progress_bar = if terminal && !args.no_progress {
+        multi.add(ProgressBar::new(test_count.try_into()?))
+    } else {
+        let progress_bar = ProgressBar::hidden();
+        progress_bar.set_length(test_count.try_into()?);
+        progress_bar
+    };
+
+    let mut progress_style = ProgressStyle::with_template(
+        "[{elapsed_precise}]▕{wide_bar}▏{pos}/{len} {percent}% ({msg}, {per_sec:!5} tests/s, ETA: {eta})",
+    )
+    .unwrap()
+    .progress_chars("█▉▊▋▌▍▎▏  ");
+
+    let eta = HistoricalEta::new(&cache, tests.iter().map(|x| x.name.as_str()), parallel);
+    if let Some(eta) = eta.clone() {
+        progress_style = progress_style
+            .with_key("eta", move |state: &ProgressState, w: &mut dyn FmtWrite| {
+                eta.render(state, w)
+            });
+    }
+
+    progress_bar.set_style(progress_style);
+    progress_bar.set_message("0 failed");
+
+    let progress_bar = Arc::new(progress_bar);
+
+    let failed_tests = Arc::new(Mutex::new(0usize));
+
+    let events = args
+        .events
+        .as_deref()
+        .map(Events::open)
+        .transpose()?
+        .map(Arc::new);
+
+    let semaphore = Arc::new(Semaphore::new(parallel));
+
+    // Admission control: each running test reserves its memory limit out of the total budget
+    let memory_budget = match (args.total_memory, args.memory_limit) {
+        (Some(total), Some(limit)) => {
+            if limit > total {
+                warn!(
+                    "The memory limit ({} MB) is larger than the total memory budget ({} MB), running one test at a time.",
+                    limit, total
+                );
+            }
+
+            let total = u32::try_from(total).unwrap_or(u32::MAX);
+            let weight = u32::try_from(limit).unwrap_or(u32::MAX).min(total);
+            Some((Arc::new(Semaphore::new(total as usize)), weight))
+        }
+        (Some(_), None) => {
+            warn!("--total-memory has no effect without a memory limit.");
+            None
+        }
+        _ => None,
+    };
+
+    info!(
+        "Loaded {} tests for task {}. Running {} tests in parallel.",
+        test_count, &args.task, parallel
+    );
+
+    // also cancelled by --fail-fast, which shouldn't cancel the caller's token
+    let cancel = stop.child_token();
+
+    // dropping the set (e.g. when the caller gives up on the run) aborts the tests, which kills
+    // their processes
+    let mut tests: JoinSet<_> = tests
+        .into_iter()
+        .map(|test| {
+            let progress_bar = progress_bar.clone();
+            let failed_tests = failed_tests.clone();
+            let semaphore = semaphore.clone();
+            let memory_budget = memory_budget.clone();
+            let eta = eta.clone();
+            let events = events.clone();
+            let cancel = cancel.clone();
+
+            let args = args.clone();
+
+            async move {
+                let run = async {
+                    let permit = semaphore.acquire().await.unwrap();
+                    let memory_permit = match &memory_budget {
+                        Some((budget, weight)) => Some(budget.acquire_many(*weight).await.unwrap()),
+                        None => None,
+                    };
+
+                    let name = test.name.clone();
+                    let mut ret = test.clone().run(&args).await;
+                    drop((permit, memory_permit));
+
+                    let mut retries = 0;
+                    while retries < args.retry_timeouts
+                        && matches!(ret, Ok(TestTimeoutResult::TimedOut(..)))
+                    {
+                        retries += 1;
+                        warn!(
+                            "↻ Test {} - timed out, retrying alone ({}/{})",
+                            name, retries, args.retry_timeouts
+                        );
+
+                        // taking all permits stops other tests from starting until it's done
+                        let _permits = semaphore.acquire_many(parallel as u32).await.unwrap();
+                        ret = test.clone().run(&args).await;
+                    }
+
+                    if let Ok(TestTimeoutResult::Finished(res)) = &mut ret {
+                        if retries > 0 && res.correct {
+                            warn!(
+                                "↻ Test {} passed after {} retries, its timeout may be flaky",
+                                name, retries
+                            );
+                            res.passed_after_retry = true;
+                        }
+                    }
+                    if let Err(e) = &ret {
+                        error!("✖ Test {} - ERROR\n{:?}", name, e);
+                    }
+
+                    let incr_failed_tests = || async {
+                        let mut failed_tests = failed_tests.lock().await;
+                        *failed_tests += 1;
+                        progress_bar.set_message(format!("{} failed", *failed_tests));
+                    };
+
+                    let failed = !matches!(&ret, Ok(TestTimeoutResult::Finished(x)) if x.correct);
+                    if failed {
+                        incr_failed_tests().await;
+                    }
+
+                    if let Some(eta) = &eta {
+                        eta.finish(&name);
+                    }
+
+                    if let Some(events) = &events {
+                        let report = match &ret {
+                            Ok(res) => TestReport::from_result(res),
+                            Err(e) => TestReport::error(&name, e),
+                        };
+
+                        if let Err(e) = events.emit(&report) {
+                            warn!("Failed to write the event of test {}: {}", name, e);
+                        }
+                    }
+
+                    progress_bar.inc(1);
+
+                    let done = progress_bar.position() as usize;
+                    if progress_lines
+                        && done < test_count
+                        && done.is_multiple_of(test_count.div_ceil(10))
+                    {
+                        info!(
+                            "Progress: {}/{} tests done, {} failed",
+                            done,
+                            test_count,
+                            *failed_tests.lock().await
+                        );
+                    }
+
+                    if failed && args.fail_fast {
+                        cancel.cancel();
+                    }
+
+                    ret.map_err(|e| (name, e))
+                };
+
+                // a cancelled test is dropped, which also kills its process
+                tokio::select! {
+                    biased;
+                    _ = cancel.cancelled() => None,
+                    ret = run => Some(ret),
+                }
+            }
+        })
+        .collect();
+
+    let mut finished = vec![];
+    while let Some(res) = tests.join_next().await {
+        finished.push(res);
+    }
+    let interrupted = stop.is_cancelled();
+
+    let mut errors = vec![];
+    let mut results: Vec<_> = finished
+        .into_iter()
+        .filter_map(|x| x.ok().flatten())
+        .filter_map(|x| x.map_err(|e| errors.push(e)).ok())
+        .collect();
+
+    if cancel.is_cancelled() {
+        info!(
+            "Stopped{}, skipped {} tests.",
+            if interrupted {
+                ""
+            } else {
+                " after the first failure"
+            },
+            test_count - results.len() - errors.len()
+        );
+    }
+
+    results.sort_by(|a, b| natural_cmp(a.name(), b.name()));
+    errors.sort_by(|(a, _), (b, _)| natural_cmp(a, b));
+
+    for test in results.iter() {
+        match test {
+            TestTimeoutResult::TimedOut(name, time_limit) => cache.set_timing(name, *time_limit),
+            TestTimeoutResult::Finished(res) => cache.set_timing(&res.name, res.time),
+        }
+        cache.set_last_run(test.name(), run_start);
+    }
+
+    progress_bar.finish();
+
+    if !args.no_cache {
+        cache.save(&cache_path).await?;
+    }
+
+    Ok(SuiteReport {
+        test_count,
+        results,
+        errors,
+        tags,
+        interrupted,
+    })
+}
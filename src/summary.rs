@@ -0,0 +1,435 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write as FmtWrite,
+    io::{self, IsTerminal, Write},
+    time::Duration,
+};
+
+use chrono::Local;
+use color_eyre::Result;
+use log::{error, info, warn};
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    baseline::Baseline,
+    diff::open_diff_tool,
+    get_tests, groups, history,
+    natural_sort::natural_cmp,
+    report::{self, JsonReport, ReportFormat},
+    tests::describe_exit_status,
+    Args, SuiteReport, TestTimeoutResult,
+};
+
+#[derive(Debug, Clone)]
+struct TestStats {
+    pub pass: Vec<String>,
+    /// Passed tests which were close to the time limit (also in `pass`)
+    pub slow_pass: Vec<String>,
+    /// Tests which only passed after retrying a timeout (also in `pass`)
+    pub passed_after_retry: Vec<String>,
+    pub fail: Vec<String>,
+    pub runtime_error: Vec<String>,
+    pub memory_limit_exceeded: Vec<String>,
+    pub timeout: Vec<String>,
+
+    /// Details about each failed test, like how similar its output was
+    pub fail_details: Vec<String>,
+    /// How long each test took, and whether it timed out (then it's the timeout)
+    pub durations: Vec<(String, Duration, bool)>,
+}
+
+impl TestStats {
+    pub fn new() -> Self {
+        Self {
+            pass: vec![],
+            slow_pass: vec![],
+            passed_after_retry: vec![],
+            fail: vec![],
+            runtime_error: vec![],
+            memory_limit_exceeded: vec![],
+            timeout: vec![],
+            fail_details: vec![],
+            durations: vec![],
+        }
+    }
+}
+
+/// Prints the tests which would be ran, warning about missing expected outputs
+pub fn list_tests(args: &Args) -> Result<bool> {
+    let tests = get_tests(args)?;
+
+    for test in tests.iter() {
+        let expected = test
+            .expected()
+            .map_or("(none)".to_string(), |x| x.to_string());
+        println!("{}: {} -> {}", test.name, test.input(), expected);
+
+        if args.expect_exit.is_none() && test.expected().is_some_and(|x| x.is_missing()) {
+            warn!(
+                "The expected output of test {} ({}) doesn't exist.",
+                test.name, expected
+            );
+        }
+    }
+
+    info!("Found {} tests for task {}.", tests.len(), args.task);
+    Ok(true)
+}
+
+/// Prints the report of the run and produces the other outputs requested by the options (e.g.
+/// `junit`, `history`, `baseline` or `diff_tool`), returning whether the run succeeded (all tests
+/// passed, or `allow_fail`)
+pub async fn report_suite(args: &Args, suite: &SuiteReport) -> Result<bool> {
+    let reports = suite.reports();
+    let SuiteReport {
+        test_count,
+        results,
+        errors,
+        tags,
+        interrupted,
+    } = suite;
+    let test_count = *test_count;
+
+    let mut stats = TestStats::new();
+
+    for test in results.iter() {
+        match test {
+            TestTimeoutResult::TimedOut(name, time_limit) => {
+                stats.timeout.push(name.to_string());
+                stats.durations.push((name.to_string(), *time_limit, true));
+            }
+            TestTimeoutResult::Finished(res) => {
+                stats.durations.push((res.name.clone(), res.time, false));
+
+                if res.correct {
+                    stats.pass.push(res.name.clone());
+                    if res.slow_pass {
+                        stats.slow_pass.push(res.name.clone());
+                    }
+                    if res.passed_after_retry {
+                        stats.passed_after_retry.push(res.name.clone());
+                    }
+                } else if res.runtime_error || res.memory_limit_exceeded {
+                    let list = if res.memory_limit_exceeded {
+                        &mut stats.memory_limit_exceeded
+                    } else {
+                        &mut stats.runtime_error
+                    };
+                    list.push(res.name.clone());
+                    stats.fail_details.push(format!(
+                        "Test {} {}{}",
+                        res.name,
+                        describe_exit_status(&res.output.status),
+                        if res.output.stdout.is_empty() {
+                            " (no output produced)"
+                        } else {
+                            ""
+                        }
+                    ));
+                } else {
+                    stats.fail.push(res.name.clone());
+                    stats
+                        .fail_details
+                        .push(match (res.similarity, args.expect_exit) {
+                            (Some(similarity), _) => format!(
+                                "Test {} ({:.1}% of lines match)",
+                                res.name,
+                                similarity * 100.0
+                            ),
+                            (None, Some(_)) => format!(
+                                "Test {} {}",
+                                res.name,
+                                describe_exit_status(&res.output.status)
+                            ),
+                            (None, None) => format!("Test {}", res.name),
+                        });
+                }
+            }
+        }
+    }
+
+    if let Some(path) = &args.junit {
+        tokio::fs::write(path, report::junit(&args.task, &reports)).await?;
+    }
+
+    let group_scores = if args.group.is_empty() {
+        vec![]
+    } else {
+        groups::score(
+            &args.group,
+            tags.keys()
+                .map(|name| (name.as_str(), stats.pass.contains(name))),
+        )
+    };
+
+    let mut summary = String::new();
+    writeln!(
+        summary,
+        "*** TEST REPORT ***\n  TOTAL: {}\n✔ PASS: {}\n✖ FAIL: {}\n✖ RUNTIME ERROR: {}\n✖ MEMORY LIMIT EXCEEDED: {}\n✖ TIMEOUT: {}",
+        test_count,
+        stats.pass.len(),
+        stats.fail.len(),
+        stats.runtime_error.len(),
+        stats.memory_limit_exceeded.len(),
+        stats.timeout.len()
+    )?;
+
+    for fail in stats.fail_details.iter() {
+        writeln!(summary, "  ✖ {}", fail)?;
+    }
+
+    for name in stats.slow_pass.iter() {
+        writeln!(summary, "  ⚠ Test {} passed close to the time limit", name)?;
+    }
+
+    for name in stats.passed_after_retry.iter() {
+        writeln!(summary, "  ↻ Test {} passed after retry", name)?;
+    }
+
+    let peak_memory = results
+        .iter()
+        .filter_map(|x| match x {
+            TestTimeoutResult::Finished(res) => Some((res.peak_memory?, &res.name)),
+            TestTimeoutResult::TimedOut(..) => None,
+        })
+        .max();
+    if let Some((bytes, name)) = peak_memory {
+        writeln!(
+            summary,
+            "  Peak memory: {:.1} MB (test {})",
+            bytes as f64 / 1024.0 / 1024.0,
+            name
+        )?;
+    }
+
+    let mut tag_counts: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for (name, test_tags) in tags.iter() {
+        let passed = stats.pass.contains(name);
+
+        for tag in test_tags {
+            let (pass, total) = tag_counts.entry(tag).or_default();
+            *pass += passed as usize;
+            *total += 1;
+        }
+    }
+
+    for (tag, (pass, total)) in tag_counts {
+        writeln!(summary, "  #{}: {}/{} passed", tag, pass, total)?;
+    }
+
+    if !group_scores.is_empty() {
+        writeln!(summary, "Subtasks:")?;
+        for group in group_scores.iter() {
+            writeln!(
+                summary,
+                "  {} {}: {}/{} passed, {}/{} points",
+                if group.total > 0 && group.passed == group.total {
+                    "✔"
+                } else {
+                    "✖"
+                },
+                group.name,
+                group.passed,
+                group.total,
+                group.awarded,
+                group.points
+            )?;
+        }
+        writeln!(
+            summary,
+            "  TOTAL: {}/{} points",
+            group_scores.iter().map(|x| x.awarded).sum::<f64>(),
+            group_scores.iter().map(|x| x.points).sum::<f64>()
+        )?;
+    }
+
+    if args.show_slowest > 0 && !stats.durations.is_empty() {
+        let mut slowest = stats.durations.clone();
+        slowest.sort_by_key(|x| std::cmp::Reverse(x.1));
+
+        writeln!(summary, "Slowest tests:")?;
+        for (name, time, timed_out) in slowest.iter().take(args.show_slowest) {
+            writeln!(
+                summary,
+                "  ⏱ Test {}: {:.2} s{}",
+                name,
+                time.as_secs_f64(),
+                if *timed_out { " (timed out)" } else { "" }
+            )?;
+        }
+    }
+
+    if args.repeat > 1 {
+        for res in results.iter().filter_map(|x| match x {
+            TestTimeoutResult::Finished(res) if res.timings.len() == args.repeat as usize => {
+                Some(res)
+            }
+            _ => None,
+        }) {
+            let mut timings = res.timings.clone();
+            timings.sort();
+
+            writeln!(
+                summary,
+                "  ⏱ Test {}: min {:.2} s, median {:.2} s, mean {:.2} s, max {:.2} s",
+                res.name,
+                timings[0].as_secs_f64(),
+                res.median_time().as_secs_f64(),
+                res.time.as_secs_f64(),
+                timings[timings.len() - 1].as_secs_f64()
+            )?;
+        }
+    }
+
+    match args.format {
+        ReportFormat::Pretty => {
+            print!("{}", summary);
+
+            if args.table {
+                let mut sorted = reports.clone();
+                sorted.sort_by(|a, b| natural_cmp(&a.name, &b.name));
+                print!("{}", report::table(&sorted, io::stdout().is_terminal()));
+            }
+        }
+        ReportFormat::Json => {
+            let report = JsonReport {
+                task: &args.task,
+                total: test_count,
+                passed: stats.pass.len(),
+                tests: &reports,
+                groups: &group_scores,
+            };
+
+            println!("{}", serde_json::to_string(&report)?);
+        }
+        ReportFormat::Tap => print!("{}", report::tap(&reports)),
+    }
+
+    if let Some(path) = &args.report_file {
+        let mut contents = format!(
+            "[{}] Task {}\n",
+            Local::now().format("%d-%m-%Y %H:%M:%S"),
+            args.task
+        );
+        for report in reports.iter() {
+            writeln!(
+                contents,
+                "  Test {} - {}{}",
+                report.name,
+                report.outcome.label(),
+                report
+                    .time_ms
+                    .map_or(String::new(), |x| format!(" ({:.2} s)", x / 1000.0))
+            )?;
+        }
+        contents.push_str(&summary);
+        contents.push('\n');
+
+        let mut file = tokio::fs::OpenOptions::new();
+        if args.overwrite_report {
+            file.write(true).create(true).truncate(true);
+        } else {
+            file.append(true).create(true);
+        }
+        file.open(path)
+            .await?
+            .write_all(contents.as_bytes())
+            .await?;
+    }
+
+    let mut all_passed = !*interrupted
+        && stats.fail.is_empty()
+        && stats.runtime_error.is_empty()
+        && stats.memory_limit_exceeded.is_empty()
+        && stats.timeout.is_empty()
+        && errors.is_empty();
+
+    if let Some(history) = args.history.clone() {
+        let slowest = results
+            .iter()
+            .map(|x| match x {
+                TestTimeoutResult::TimedOut(_, time_limit) => *time_limit,
+                TestTimeoutResult::Finished(res) => res.time,
+            })
+            .max()
+            .unwrap_or_default();
+
+        history::append(history, &args.task, stats.pass.len(), test_count, slowest).await?;
+    }
+
+    if let Some(dir) = &args.save_failures {
+        tokio::fs::create_dir_all(dir).await?;
+
+        for res in results.iter().filter_map(|x| match x {
+            TestTimeoutResult::Finished(res) if !res.correct => Some(res),
+            _ => None,
+        }) {
+            let name = res.name.replace(['/', '\\'], "_");
+            tokio::fs::write(dir.join(format!("{}.out", name)), &res.output.stdout).await?;
+
+            if !res.stderr.is_empty() {
+                tokio::fs::write(dir.join(format!("{}.err", name)), &res.stderr).await?;
+            }
+        }
+
+        info!("Saved the outputs of failed tests to {}.", dir.display());
+    }
+
+    if let Some(tool) = &args.diff_tool {
+        let failures = results.iter().filter_map(|x| match x {
+            TestTimeoutResult::Finished(res) if !res.correct => Some((res, res.expected.as_ref()?)),
+            _ => None,
+        });
+
+        for (res, expected) in failures.take(if args.diff_first_only { 1 } else { usize::MAX }) {
+            info!("Opening test {} in the diff tool...", res.name);
+            open_diff_tool(tool, &res.name, expected, &res.output.stdout).await?;
+        }
+    }
+
+    if let Some(path) = &args.baseline {
+        let timings = results.iter().filter_map(|x| match x {
+            TestTimeoutResult::TimedOut(..) => None,
+            // the median, so a single slow repetition isn't reported as a regression
+            TestTimeoutResult::Finished(res) => Some((res.name.as_str(), res.median_time())),
+        });
+
+        if args.update_baseline {
+            Baseline::new(timings).save(path).await?;
+            info!("Saved the timings as the baseline to {}.", path.display());
+        } else {
+            let regressions = Baseline::load(path)
+                .await?
+                .regressions(timings, args.regression_threshold);
+
+            if !regressions.is_empty() {
+                // keep stdout clean for the JSON and TAP reports
+                let mut out: Box<dyn Write> = match args.format {
+                    ReportFormat::Pretty => Box::new(io::stdout()),
+                    ReportFormat::Json | ReportFormat::Tap => Box::new(io::stderr()),
+                };
+
+                writeln!(out, "✖ REGRESSIONS: {}", regressions.len())?;
+                for regression in regressions.iter() {
+                    writeln!(
+                        out,
+                        "  ✖ Test {}: {:.2} s (baseline: {:.2} s, +{:.0}%)",
+                        regression.name,
+                        regression.time.as_secs_f64(),
+                        regression.baseline.as_secs_f64(),
+                        regression.slowdown()
+                    )?;
+                }
+
+                error!(
+                    "{} tests got more than {}% slower than the baseline",
+                    regressions.len(),
+                    args.regression_threshold
+                );
+                all_passed = false;
+            }
+        }
+    }
+
+    Ok(all_passed || args.allow_fail)
+}
@@ -0,0 +1,141 @@
+use std::{collections::HashMap, path::Path};
+
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
+};
+use serde_json::Value;
+
+/// A test generated by substituting a row of parameters into the template
+#[derive(Debug, Clone)]
+pub struct TemplateTest {
+    pub name: String,
+    pub input: Vec<u8>,
+    pub expected: Option<Vec<u8>>,
+}
+
+type Params = HashMap<String, String>;
+
+/// Replaces each `{{param}}` in the template with the value of the parameter.
+/// Placeholders of unknown parameters are left as they are. The template is scanned once, so
+/// placeholders inside the substituted values aren't expanded.
+fn substitute(template: &str, params: &Params) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let placeholder = &rest[start..start + len + 4];
+
+        result.push_str(&rest[..start]);
+
+        match params.get(&placeholder[2..placeholder.len() - 2]) {
+            Some(value) => result.push_str(value),
+            None => result.push_str(placeholder),
+        }
+
+        rest = &rest[start + placeholder.len()..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// Splits a CSV line into fields. Fields may be quoted with `"`, with `""` being an escaped quote.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field).trim().to_string()),
+            c => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+
+    fields
+}
+
+fn parse_csv(contents: &str) -> Result<Vec<Params>> {
+    let mut lines = contents.lines().filter(|x| !x.trim().is_empty());
+
+    let header = split_csv_line(lines.next().ok_or_else(|| eyre!("Missing CSV header"))?);
+
+    lines
+        .enumerate()
+        .map(|(i, line)| {
+            let fields = split_csv_line(line);
+            if fields.len() != header.len() {
+                bail!(
+                    "Row {} has {} fields, but the header has {}",
+                    i + 1,
+                    fields.len(),
+                    header.len()
+                );
+            }
+
+            Ok(header.iter().cloned().zip(fields).collect())
+        })
+        .collect()
+}
+
+fn parse_json(contents: &str) -> Result<Vec<Params>> {
+    let rows: Vec<serde_json::Map<String, Value>> = serde_json::from_str(contents)?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            row.into_iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        Value::String(x) => x,
+                        x => x.to_string(),
+                    };
+                    (key, value)
+                })
+                .collect()
+        })
+        .collect())
+}
+
+/// Generates a test for each row of the parameters file (JSON if its extension is `.json`,
+/// CSV otherwise). Tests are named after the `name` parameter or, if missing, the row number.
+pub fn generate_tests(
+    template: &Path,
+    params: &Path,
+    out_template: Option<&Path>,
+) -> Result<Vec<TemplateTest>> {
+    let template = std::fs::read_to_string(template)?;
+    let out_template = out_template.map(std::fs::read_to_string).transpose()?;
+
+    let contents = std::fs::read_to_string(params)?;
+    let rows = match params.extension().and_then(|x| x.to_str()) {
+        Some("json") => parse_json(&contents)?,
+        _ => parse_csv(&contents)?,
+    };
+
+    Ok(rows
+        .iter()
+        .enumerate()
+        .map(|(i, row)| TemplateTest {
+            name: row
+                .get("name")
+                .cloned()
+                .unwrap_or_else(|| (i + 1).to_string()),
+            input: substitute(&template, row).into_bytes(),
+            expected: out_template
+                .as_ref()
+                .map(|x| substitute(x, row).into_bytes()),
+        })
+        .collect())
+}
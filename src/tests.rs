@@ -1,76 +1,414 @@
 use std::{
-    path::PathBuf,
-    process::{Output, Stdio},
-    time::{Duration, Instant},
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    process::{ExitStatus, Output, Stdio},
+    time::{Duration, Instant, SystemTime},
 };
 
-use color_eyre::{eyre::ContextCompat, Report, Result};
+use color_eyre::{
+    eyre::{bail, eyre, ContextCompat},
+    Result,
+};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use glob::glob;
 use itertools::Itertools;
-use log::{debug, error, info};
-use tokio::{fs, io::AsyncWriteExt, process::Command, time::timeout};
+use log::{debug, error, info, log, warn, Level};
+use tempfile::TempDir;
+use tokio::{
+    fs,
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    process::{Child, ChildStderr, ChildStdin, ChildStdout, Command},
+    time::timeout,
+};
 
-use crate::slice_trim_ext::SliceTrimExt;
+use crate::{
+    command::{parse_command, run_with_input},
+    compare::{compare, TrimMode},
+    diff::{first_difference, render_diff, similarity},
+    natural_sort::natural_cmp,
+    slice_trim_ext::SliceTrimExt,
+    template::generate_tests,
+};
 
 #[derive(Debug, Clone)]
 pub struct Test {
     pub name: String,
+    pub tags: Vec<String>,
+    /// Overrides the global timeout, see `read_timeout`
+    pub timeout: Option<Duration>,
+    /// Overrides `--expected-exit`, see `read_exit_code`
+    pub exit_code: Option<i32>,
+
+    input: TestData,
+    /// `None` for generated tests without an expected output
+    expected: Option<TestData>,
+}
+
+/// Where the input or expected output of a test comes from
+#[derive(Debug, Clone)]
+pub enum TestData {
+    File(PathBuf),
+
+    /// Generated in memory, e.g. from a template
+    Memory(Vec<u8>),
+}
+
+impl TestData {
+    /// Reads the data, decompressing `.gz` files
+    async fn read(&self) -> Result<Vec<u8>> {
+        match self {
+            TestData::File(path) if is_gzip(path) => {
+                let path = path.clone();
+
+                tokio::task::spawn_blocking(move || {
+                    let mut data = vec![];
+                    std::io::Read::read_to_end(
+                        &mut GzDecoder::new(std::fs::File::open(&path)?),
+                        &mut data,
+                    )?;
+                    Ok(data)
+                })
+                .await?
+            }
+            TestData::File(path) => Ok(fs::read(path).await?),
+            TestData::Memory(data) => Ok(data.clone()),
+        }
+    }
+
+    /// Opens the data for reading, without reading a plain file into memory at once
+    async fn open(&self) -> Result<Box<dyn AsyncRead + Unpin + Send + '_>> {
+        match self {
+            TestData::File(path) if !is_gzip(path) => Ok(Box::new(fs::File::open(path).await?)),
+            TestData::File(_) => Ok(Box::new(std::io::Cursor::new(self.read().await?))),
+            TestData::Memory(data) => Ok(Box::new(data.as_slice())),
+        }
+    }
+
+    /// The path of the file, unless it's compressed and would have to be decompressed first
+    fn plain_path(&self) -> Option<&Path> {
+        match self {
+            TestData::File(path) if !is_gzip(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Whether this is a file which doesn't exist
+    pub fn is_missing(&self) -> bool {
+        match self {
+            TestData::File(path) => !path.exists(),
+            TestData::Memory(_) => false,
+        }
+    }
+
+    /// Whether the file was modified after the given time. Data in memory always counts as modified.
+    fn modified_since(&self, time: SystemTime) -> bool {
+        match self {
+            TestData::File(path) => std::fs::metadata(path)
+                .and_then(|x| x.modified())
+                .is_ok_and(|x| x > time),
+            TestData::Memory(_) => true,
+        }
+    }
+}
 
-    in_file: PathBuf,
-    out_file: PathBuf,
+impl std::fmt::Display for TestData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TestData::File(path) => write!(f, "{}", path.display()),
+            TestData::Memory(_) => write!(f, "(generated)"),
+        }
+    }
 }
 
 impl Test {
+    /// Runs the test `--repeat` times, stopping at the first repetition which doesn't pass
     pub async fn run(self, args: &crate::Args) -> Result<TestTimeoutResult> {
-        let command = args.command.clone().unwrap_or_else(|| {
-            if cfg!(windows) {
-                format!("{}.exe", args.task.clone())
-            } else {
-                args.task.clone()
+        let mut timings = vec![];
+        let mut peak_memory = None;
+        let mut slow_pass = false;
+
+        for repetition in 1..=args.repeat {
+            let result = self
+                .clone()
+                .run_once(args, repetition == args.repeat)
+                .await?;
+
+            match result {
+                TestTimeoutResult::Finished(mut res) if res.correct => {
+                    timings.push(res.time);
+                    peak_memory = peak_memory.max(res.peak_memory);
+                    slow_pass |= res.slow_pass;
+
+                    if repetition == args.repeat {
+                        res.time = timings.iter().sum::<Duration>() / timings.len() as u32;
+                        res.timings = timings;
+                        res.peak_memory = peak_memory;
+                        res.slow_pass = slow_pass;
+                        return Ok(TestTimeoutResult::Finished(res));
+                    }
+                }
+                result => return Ok(result),
             }
-        });
+        }
+
+        unreachable!("--repeat is at least 1")
+    }
+
+    /// Runs the test once. If it passes, it's only logged if `log_pass` is set.
+    async fn run_once(self, args: &crate::Args, log_pass: bool) -> Result<TestTimeoutResult> {
+        let command = program(args);
 
         debug!("Running test {}...", &self.name);
-        let start_time = Instant::now();
 
-        let res = timeout(args.timeout, async {
-            let mut child = Command::new(command)
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .kill_on_drop(true)
-                .spawn()?;
+        let time_limit = self.timeout.unwrap_or(args.timeout);
 
-            {
-                let mut stdin = child.stdin.take().context("Failed to take child's stdin")?;
+        let mut attempt = 0;
+        let res = loop {
+            let res = self.execute(&command, args, time_limit).await?;
 
-                stdin.write(&self.get_input().await?).await?;
+            if let Some(execution) = &res {
+                if let Some(code) = execution.output.status.code() {
+                    if attempt < args.retries && args.retry_on_exit.contains(&code) {
+                        attempt += 1;
+                        warn!(
+                            "↻ Test {} - exited with code {}, retrying ({}/{})",
+                            &self.name, code, attempt, args.retries
+                        );
+                        continue;
+                    }
+                }
             }
 
-            Ok::<_, Report>(child.wait_with_output().await?)
-        })
-        .await;
-
-        let elapsed = start_time.elapsed();
+            break res;
+        };
 
         Ok(match res {
-            Ok(output) => {
-                let output = output?;
-                let correct = self.is_correct(output.stdout.clone()).await?;
+            Some(execution) => {
+                let Execution {
+                    mut output,
+                    time: elapsed,
+                    output_limit_exceeded,
+                    peak_memory,
+                    interactor_rejection,
+                } = execution;
+                let stderr = std::mem::take(&mut output.stderr);
+                let usage = describe_usage(elapsed, peak_memory);
 
-                if correct {
-                    info!(
-                        "✔ Test {} - PASS ({:.2} s)",
+                let expected_exit = self.exit_code.or(args.expected_exit);
+
+                // in --expect-exit mode, the exit code is what's being checked, and with an
+                // expected exit code, exiting with another one is a wrong answer instead
+                // (the program was killed if it exceeded the output limit, and it may crash
+                // because a rejecting interactor stopped talking to it)
+                let runtime_error = args.expect_exit.is_none()
+                    && !output.status.success()
+                    && (expected_exit.is_none() || output.status.code().is_none())
+                    && !output_limit_exceeded
+                    && interactor_rejection.is_none();
+
+                let memory_limit_exceeded = runtime_error
+                    && args.memory_limit.is_some()
+                    && looks_like_out_of_memory(&output.status, &stderr);
+
+                if args.accept {
+                    // a crashed or cut off output would make a wrong golden file
+                    let refusal = if output_limit_exceeded {
+                        Some(format!(
+                            "Output limit exceeded, the program printed more than {} bytes",
+                            args.output_limit
+                        ))
+                    } else if runtime_error {
+                        Some(format!(
+                            "The program {}",
+                            describe_exit_status(&output.status)
+                        ))
+                    } else {
+                        None
+                    };
+
+                    match &refusal {
+                        Some(refusal) => error!(
+                            "✖ Test {} - NOT ACCEPTED ({})\n{}{}",
+                            &self.name,
+                            usage,
+                            refusal,
+                            stderr_note(&stderr)
+                        ),
+                        None => {
+                            self.accept(&output.stdout, args.trim).await?;
+                            info!("✔ Test {} - ACCEPTED ({})", &self.name, usage);
+                        }
+                    }
+
+                    return Ok(TestTimeoutResult::Finished(TestResult {
+                        name: self.name.clone(),
+
+                        time: elapsed,
+                        timings: vec![elapsed],
+                        correct: refusal.is_none(),
+                        runtime_error: runtime_error && !memory_limit_exceeded,
+                        memory_limit_exceeded,
+                        slow_pass: false,
+                        passed_after_retry: false,
+                        peak_memory,
+
+                        expected: None,
+                        similarity: None,
+                        output,
+                        stderr,
+                    }));
+                }
+
+                // the output is judged by the checker or interactor instead of comparing it
+                let judged = args.checker.is_some() || args.interactor.is_some();
+
+                let expected = match (args.expect_exit, &self.expected, judged) {
+                    (Some(_), _, _) => None,
+                    // a checker or interactor may not need the expected output
+                    (None, None, true) => Some(vec![]),
+                    (None, Some(expected), true) if expected.is_missing() => Some(vec![]),
+                    _ => Some(self.get_output().await?),
+                };
+
+                let rejection = if output_limit_exceeded {
+                    Some(format!(
+                        "Output limit exceeded, the program printed more than {} bytes",
+                        args.output_limit
+                    ))
+                } else if interactor_rejection.is_some() {
+                    interactor_rejection
+                } else if let Some(code) =
+                    expected_exit.filter(|&x| !runtime_error && output.status.code() != Some(x))
+                {
+                    Some(format!(
+                        "Expected exit code {}, but the program {}",
+                        code,
+                        describe_exit_status(&output.status)
+                    ))
+                } else if args.check_determinism && !runtime_error {
+                    self.check_determinism(&command, args, &output.stdout)
+                        .await?
+                } else {
+                    None
+                };
+
+                let rejection = match (rejection, &args.output_validator) {
+                    (None, Some(validator)) if !runtime_error => {
+                        validate_output(validator, &output.stdout).await?
+                    }
+                    (rejection, _) => rejection,
+                };
+
+                let rejection = match (rejection, &args.checker, &expected) {
+                    (None, Some(checker), Some(expected)) if !runtime_error => {
+                        self.run_checker(checker, expected, &output.stdout).await?
+                    }
+                    (rejection, _, _) => rejection,
+                };
+
+                let correct = !runtime_error
+                    && rejection.is_none()
+                    && match &expected {
+                        // the checker or interactor has already accepted the output
+                        Some(_) if judged => true,
+                        Some(expected) => self.is_correct(expected, &output.stdout, args).await?,
+                        None => output.status.code() == args.expect_exit,
+                    };
+
+                let similarity = match &expected {
+                    Some(expected)
+                        if !correct && !runtime_error && !output_limit_exceeded && !judged =>
+                    {
+                        Some(similarity(expected.trim(), output.stdout.trim()))
+                    }
+                    _ => None,
+                };
+
+                // it would likely time out on a slower judge
+                let slow_pass =
+                    correct && elapsed.as_secs_f64() > time_limit.as_secs_f64() * args.warn_ratio;
+
+                let stderr_note = stderr_note(&stderr);
+
+                if memory_limit_exceeded {
+                    error!(
+                        "✖ Test {} - MEMORY LIMIT EXCEEDED ({})\nThe program {}{}",
                         &self.name,
-                        &elapsed.as_secs_f64()
+                        usage,
+                        describe_exit_status(&output.status),
+                        stderr_note,
+                    );
+                } else if runtime_error {
+                    error!(
+                        "✖ Test {} - RUNTIME ERROR ({})\n{} {}{}",
+                        &self.name,
+                        usage,
+                        if output.stdout.is_empty() {
+                            "No output produced, the program"
+                        } else {
+                            "The program"
+                        },
+                        describe_exit_status(&output.status),
+                        stderr_note,
+                    );
+                } else if let Some(rejection) = &rejection {
+                    error!(
+                        "✖ Test {} - FAIL ({})\n{}{}",
+                        &self.name, usage, rejection, stderr_note,
+                    );
+                } else if slow_pass {
+                    log!(
+                        if log_pass { Level::Warn } else { Level::Debug },
+                        "⚠ Test {} - SLOW PASS ({}, {:.0}% of the {:.2} s time limit)",
+                        &self.name,
+                        usage,
+                        elapsed.as_secs_f64() / time_limit.as_secs_f64() * 100.0,
+                        time_limit.as_secs_f64()
+                    );
+                } else if correct {
+                    log!(
+                        if log_pass { Level::Info } else { Level::Debug },
+                        "✔ Test {} - PASS ({})",
+                        &self.name,
+                        usage
+                    );
+                } else if let Some(code) = args.expect_exit {
+                    error!(
+                        "✖ Test {} - FAIL ({})\nExpected exit code {}, but the program {}{}",
+                        &self.name,
+                        usage,
+                        code,
+                        describe_exit_status(&output.status),
+                        stderr_note,
                     );
                 } else {
                     error!(
-                        "✖ Test {} - FAIL ({:.2} s)\nExpected: {}\nGot: {}",
+                        "✖ Test {} - FAIL ({}, {:.1}% of lines match)\nDiff (- expected, + got):\n{}{}",
                         &self.name,
-                        &elapsed.as_secs_f64(),
-                        String::from_utf8(self.get_output().await?.as_slice().trim().to_vec())?,
-                        String::from_utf8(output.stdout.clone().as_slice().trim().to_vec())?,
+                        usage,
+                        similarity.unwrap_or_default() * 100.0,
+                        render_diff(
+                            // the output may not be valid UTF-8, but should still be shown
+                            &String::from_utf8_lossy(
+                                expected.as_deref().unwrap_or_default().trim()
+                            ),
+                            &String::from_utf8_lossy(output.stdout.trim()),
+                            args.diff_hunks,
+                        ),
+                        stderr_note,
+                    );
+                }
+
+                if args.only.is_some() {
+                    let stdin = self.get_input().await?;
+                    info!(
+                        "Input:\n{}\nExpected output:\n{}\nOutput:\n{}\nStderr:\n{}",
+                        String::from_utf8_lossy(&stdin),
+                        expected
+                            .as_ref()
+                            .map_or("(none)".into(), |x| String::from_utf8_lossy(x)),
+                        String::from_utf8_lossy(&output.stdout),
+                        String::from_utf8_lossy(&stderr),
                     );
                 }
 
@@ -78,65 +416,995 @@ impl Test {
                     name: self.name.clone(),
 
                     time: elapsed,
+                    timings: vec![elapsed],
                     correct,
+                    runtime_error: runtime_error && !memory_limit_exceeded,
+                    memory_limit_exceeded,
+                    slow_pass,
+                    passed_after_retry: false,
+                    peak_memory,
 
-                    stdin: self.get_input().await?,
+                    expected,
+                    similarity,
                     output,
+                    stderr,
                 })
             }
-            Err(_) => {
+            None => {
                 error!("✖ Test {} - TIMED OUT!", &self.name);
-                TestTimeoutResult::TimedOut(self.name)
+                TestTimeoutResult::TimedOut(self.name, time_limit)
             }
         })
     }
 
+    /// Runs the program on the test, returning `None` if it didn't exit within the time limit.
+    /// The time is measured from starting the program until it exits, while its input is
+    /// written and its output read concurrently.
+    async fn execute(
+        &self,
+        command: &str,
+        args: &crate::Args,
+        time_limit: Duration,
+    ) -> Result<Option<Execution>> {
+        let workdir = workdir(args);
+
+        let mut command = match &workdir {
+            Some(dir) => {
+                let mut command = Command::new(resolve_program(command)?);
+                command.current_dir(dir);
+                command
+            }
+            None => Command::new(command),
+        };
+        command
+            .args(&args.program_args)
+            .envs(args.env.iter().cloned())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        #[cfg(unix)]
+        if let Some(memory_limit) = args.memory_limit {
+            limit_memory(&mut command, memory_limit * 1024 * 1024);
+        }
+
+        if log::log_enabled!(Level::Debug) {
+            let stdin = match &args.interactor {
+                Some(interactor) => format!("the interactor {}", interactor),
+                None => self.input.to_string(),
+            };
+
+            let env = args
+                .env
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, shell_quote(value)))
+                .collect::<Vec<_>>();
+
+            debug!(
+                "Test {}: running `{}` in {}{}, stdin from {}",
+                self.name,
+                command_line(command.as_std()),
+                match workdir {
+                    Some(dir) => dir,
+                    None => std::env::current_dir()?,
+                }
+                .display(),
+                if env.is_empty() {
+                    String::new()
+                } else {
+                    format!(" with {}", env.join(" "))
+                },
+                stdin
+            );
+        }
+
+        // opened (and decompressed, for compressed inputs) before the clock starts, like the
+        // interactor's files and the interactor itself
+        let (input, interaction) = match &args.interactor {
+            Some(interactor) => (None, Some(self.start_interactor(interactor).await?)),
+            None => (Some(self.input.open().await?), None),
+        };
+
+        let mut child = command.spawn()?;
+        let start_time = Instant::now();
+        let own_peak_memory = own_peak_memory();
+
+        let mut stdin = child.stdin.take().context("Failed to take child's stdin")?;
+        let stdout = child
+            .stdout
+            .take()
+            .context("Failed to take child's stdout")?;
+        let mut stderr = child
+            .stderr
+            .take()
+            .context("Failed to take child's stderr")?;
+
+        let pid = child.id();
+
+        let run = async {
+            let (stdout, stderr, interactor_rejection) = match (interaction, input) {
+                (Some(interaction), _) => {
+                    interact(interaction, args, stdin, stdout, stderr).await?
+                }
+                (None, None) => unreachable!("the input is opened without an interactor"),
+                (None, Some(mut input)) => {
+                    // streamed while reading the output, so neither pipe can fill up and block
+                    let (written, stdout, stderr) = tokio::join!(
+                        async move {
+                            let res = tokio::io::copy(&mut input, &mut stdin).await;
+                            drop(stdin);
+                            res
+                        },
+                        async {
+                            let res = read_limited(stdout, args.output_limit).await;
+                            if let Ok((_, true)) = res {
+                                // stop the program from printing any more
+                                let _ = child.start_kill();
+                            }
+                            res
+                        },
+                        read_stderr(&mut stderr, args.output_limit),
+                    );
+
+                    // the program doesn't have to read all of its input
+                    match written {
+                        Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => Err(e)?,
+                        _ => {}
+                    }
+
+                    (stdout?, stderr?, None)
+                }
+            };
+
+            // the program has most likely closed its output because it's exiting,
+            // which is the last chance to sample it
+            let last_sample = read_peak_memory(pid);
+            let (status, max_rss) = wait_for_exit(&mut child, own_peak_memory).await?;
+
+            Result::<_>::Ok((
+                stdout,
+                stderr,
+                status,
+                start_time.elapsed(),
+                max_rss,
+                last_sample,
+                interactor_rejection,
+            ))
+        };
+        tokio::pin!(run);
+
+        // where the OS doesn't report the peak memory usage on exit, it's only known while the
+        // program is running, so sample it. The first sample is taken after an interval, as
+        // right after starting, the program hasn't allocated its memory yet.
+        let mut interval = tokio::time::interval_at(
+            tokio::time::Instant::now() + MEMORY_SAMPLE_INTERVAL,
+            MEMORY_SAMPLE_INTERVAL,
+        );
+        let mut peak_memory = None;
+        let sample = async {
+            loop {
+                tokio::select! {
+                    res = &mut run => break res,
+                    _ = interval.tick() => peak_memory = peak_memory.max(read_peak_memory(pid)),
+                }
+            }
+        };
+
+        // a program which timed out is killed when it's dropped
+        let Ok(res) = timeout(time_limit, sample).await else {
+            return Ok(None);
+        };
+        let (
+            (stdout, output_limit_exceeded),
+            stderr,
+            status,
+            time,
+            max_rss,
+            last_sample,
+            interactor_rejection,
+        ) = res?;
+
+        Ok(Some(Execution {
+            output: Output {
+                status,
+                stdout,
+                stderr,
+            },
+            time,
+            output_limit_exceeded,
+            peak_memory: max_rss.or(peak_memory.max(last_sample)),
+            interactor_rejection,
+        }))
+    }
+
+    /// Starts the interactor as `<interactor> <input> <output> <answer>` (the order used by
+    /// testlib), writing the files it needs to a temporary directory first
+    async fn start_interactor(&self, interactor: &str) -> Result<Interaction> {
+        let dir = tempfile::tempdir()?;
+        let input = data_path(Some(&self.input), dir.path(), "input").await?;
+        let answer = data_path(self.expected.as_ref(), dir.path(), "answer").await?;
+        let output = dir.path().join("output");
+
+        let process = parse_command(interactor)?
+            .arg(input)
+            .arg(&output)
+            .arg(answer)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| eyre!("Failed to run the interactor: {}", e))?;
+
+        Ok(Interaction {
+            _dir: dir,
+            output,
+            process,
+        })
+    }
+
+    /// Whether the input or output file of this test was modified after the given time
+    pub fn modified_since(&self, time: SystemTime) -> bool {
+        self.input.modified_since(time)
+            || self
+                .expected
+                .as_ref()
+                .is_some_and(|x| x.modified_since(time))
+    }
+
+    pub fn input(&self) -> &TestData {
+        &self.input
+    }
+
+    pub fn expected(&self) -> Option<&TestData> {
+        self.expected.as_ref()
+    }
+
     async fn get_input(&self) -> Result<Vec<u8>> {
-        Ok(fs::read(&self.in_file).await?)
+        self.input.read().await
     }
 
     async fn get_output(&self) -> Result<Vec<u8>> {
-        Ok(fs::read(&self.out_file).await?)
+        self.expected
+            .as_ref()
+            .context(format!("Test {} has no expected output", self.name))?
+            .read()
+            .await
+    }
+
+    /// Saves the output of the program (trimmed according to `--trim`) as the expected output
+    /// of this test
+    async fn accept(&self, actual: &[u8], trim: TrimMode) -> Result<()> {
+        let Some(TestData::File(out_file)) = &self.expected else {
+            bail!("Can't accept the output of generated test {}", self.name);
+        };
+
+        if let Some(parent) = out_file.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let mut contents = trim.normalize(actual).into_owned();
+        if trim != TrimMode::None {
+            contents.push(b'\n');
+        }
+
+        if is_gzip(out_file) {
+            let mut encoder = GzEncoder::new(vec![], Compression::default());
+            std::io::Write::write_all(&mut encoder, &contents)?;
+            contents = encoder.finish()?;
+        }
+
+        Ok(fs::write(out_file, contents).await?)
+    }
+
+    /// Runs the test a second time and compares both outputs using the configured comparison.
+    /// Returns a description of the difference if they don't match.
+    async fn check_determinism(
+        &self,
+        command: &str,
+        args: &crate::Args,
+        first: &[u8],
+    ) -> Result<Option<String>> {
+        let second = match self
+            .execute(command, args, self.timeout.unwrap_or(args.timeout))
+            .await?
+        {
+            Some(execution) => execution.output.stdout,
+            None => {
+                return Ok(Some(
+                    "Nondeterministic: the second run timed out".to_string(),
+                ))
+            }
+        };
+
+        if self.is_correct(first, &second, args).await? {
+            return Ok(None);
+        }
+
+        let (line, column) = first_difference(first.trim(), second.trim());
+        Ok(Some(format!(
+            "Nondeterministic: the output of the second run differs at line {}, column {}",
+            line, column
+        )))
+    }
+
+    /// Runs the checker as `<checker> <input> <output> <answer>` (the order used by testlib),
+    /// where all three are paths to files.
+    /// Returns the checker's message if it rejected the output (exited with a non-zero code),
+    /// and an error if it crashed.
+    async fn run_checker(
+        &self,
+        checker: &str,
+        expected: &[u8],
+        actual: &[u8],
+    ) -> Result<Option<String>> {
+        let dir = tempfile::tempdir()?;
+
+        let input = data_path(Some(&self.input), dir.path(), "input").await?;
+
+        let answer = match self.expected.as_ref().and_then(TestData::plain_path) {
+            Some(path) => path.to_path_buf(),
+            None => {
+                let path = dir.path().join("answer");
+                fs::write(&path, expected).await?;
+                path
+            }
+        };
+
+        let output = dir.path().join("output");
+        fs::write(&output, actual).await?;
+
+        let res = parse_command(checker)?
+            .arg(input)
+            .arg(output)
+            .arg(answer)
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .map_err(|e| eyre!("Failed to run the checker: {}", e))?;
+
+        let message = match String::from_utf8_lossy(res.stderr.trim()) {
+            x if x.is_empty() => String::new(),
+            x => format!(":\n{}", x),
+        };
+
+        if res.status.success() {
+            debug!("The checker accepted test {}{}", self.name, message);
+            return Ok(None);
+        }
+
+        if res.status.code().is_none() {
+            bail!(
+                "The checker crashed, it {}{}",
+                describe_exit_status(&res.status),
+                message
+            );
+        }
+
+        Ok(Some(format!(
+            "Rejected by the checker, which {}{}",
+            describe_exit_status(&res.status),
+            message
+        )))
+    }
+
+    async fn is_correct(&self, expected: &[u8], actual: &[u8], args: &crate::Args) -> Result<bool> {
+        if let Some(comparator) = &args.comparator {
+            return run_comparator(comparator, expected, actual).await;
+        }
+
+        Ok(compare(expected, actual, args))
+    }
+}
+
+/// Runs the comparator as `<comparator> <expected> <actual>`, returning whether it exited with 0.
+/// Both are written to files in a temporary directory, which is removed once it exits.
+async fn run_comparator(comparator: &str, expected: &[u8], actual: &[u8]) -> Result<bool> {
+    let dir = tempfile::tempdir()?;
+
+    let expected_path = dir.path().join("expected");
+    fs::write(&expected_path, expected).await?;
+    let actual_path = dir.path().join("actual");
+    fs::write(&actual_path, actual).await?;
+
+    let res = parse_command(comparator)?
+        .arg(expected_path)
+        .arg(actual_path)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|e| eyre!("Failed to run the comparator: {}", e))?;
+
+    if res.status.code().is_none() {
+        bail!(
+            "The comparator crashed, it {}:\n{}",
+            describe_exit_status(&res.status),
+            String::from_utf8_lossy(res.stderr.trim())
+        );
+    }
+
+    Ok(res.status.success())
+}
+
+/// Formats the command as it would be typed in a shell
+fn command_line(command: &std::process::Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|x| shell_quote(&x.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Quotes a word if it's empty or contains whitespace or quotes
+fn shell_quote(word: &str) -> String {
+    if word.is_empty() || word.contains(char::is_whitespace) || word.contains(['"', '\'']) {
+        format!("{:?}", word)
+    } else {
+        word.to_string()
+    }
+}
+
+/// The command to run, defaulting to the task name (with .exe on Windows)
+pub fn program(args: &crate::Args) -> String {
+    args.command.clone().unwrap_or_else(|| {
+        if cfg!(windows) {
+            format!("{}.exe", args.task.clone())
+        } else {
+            args.task.clone()
+        }
+    })
+}
+
+/// The directory to run the program in, if it's not the current one
+pub(crate) fn workdir(args: &crate::Args) -> Option<PathBuf> {
+    args.workdir
+        .as_ref()
+        .map(|x| PathBuf::from(x.replace("{task}", &args.task)))
+}
+
+/// Makes a relative path to the program (like `./mytask`, but not a bare name looked up in
+/// `PATH`) absolute, so it doesn't depend on the directory the program is ran in
+fn resolve_program(program: &str) -> Result<PathBuf> {
+    let path = Path::new(program);
+
+    if path.is_relative() && path.components().count() > 1 {
+        Ok(std::env::current_dir()?.join(path.strip_prefix(".").unwrap_or(path)))
+    } else {
+        Ok(path.to_path_buf())
+    }
+}
+
+/// Reads everything from the pipe, keeping at most `limit` bytes.
+/// Returns whether there was more to read than that.
+async fn read_limited(
+    reader: impl AsyncRead + Unpin,
+    limit: u64,
+) -> std::io::Result<(Vec<u8>, bool)> {
+    let mut buf = vec![];
+    let mut reader = reader.take(limit.saturating_add(1));
+    reader.read_to_end(&mut buf).await?;
+
+    let exceeded = buf.len() as u64 > limit;
+    buf.truncate(limit as usize);
+
+    Ok((buf, exceeded))
+}
+
+/// Reads the program's stderr, keeping at most `limit` bytes. A lot of debug output isn't
+/// a reason to fail, so the rest is just discarded.
+async fn read_stderr(stderr: &mut ChildStderr, limit: u64) -> std::io::Result<Vec<u8>> {
+    let (buf, exceeded) = read_limited(&mut *stderr, limit).await?;
+    if exceeded {
+        tokio::io::copy(stderr, &mut tokio::io::sink()).await?;
+    }
+
+    Ok(buf)
+}
+
+/// Forwards everything from the reader to the writer, returning what was forwarded (at most
+/// `limit` bytes) and whether there was more than that. Then, reading is stopped, which kills
+/// a program writing any more with SIGPIPE. The writer closing early isn't an error, the rest
+/// is only not forwarded.
+async fn forward(
+    mut reader: impl AsyncRead + Unpin,
+    writer: impl AsyncWrite + Unpin,
+    limit: u64,
+) -> std::io::Result<(Vec<u8>, bool)> {
+    let mut writer = Some(writer);
+    let mut forwarded = vec![];
+    let mut buf = [0; 8192];
+
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            return Ok((forwarded, false));
+        }
+
+        if forwarded.len() as u64 + n as u64 > limit {
+            let rest = (limit - forwarded.len() as u64) as usize;
+            forwarded.extend_from_slice(&buf[..rest]);
+            return Ok((forwarded, true));
+        }
+        forwarded.extend_from_slice(&buf[..n]);
+
+        if let Some(w) = &mut writer {
+            if w.write_all(&buf[..n]).await.is_err() {
+                writer = None;
+            }
+        }
+    }
+}
+
+/// A running interactor, see [`Test::start_interactor`]
+struct Interaction {
+    /// Removed once the interaction is over
+    _dir: TempDir,
+    /// The file the interactor may write its verdict to
+    output: PathBuf,
+    process: Child,
+}
+
+/// Connects the interactor's stdin and stdout to the program's stdout and stdin.
+/// Returns what the program sent to the interactor (and whether it exceeded the output limit)
+/// and its stderr, and the interactor's message if it rejected the interaction.
+async fn interact(
+    mut interaction: Interaction,
+    args: &crate::Args,
+    stdin: ChildStdin,
+    stdout: ChildStdout,
+    mut stderr: ChildStderr,
+) -> Result<((Vec<u8>, bool), Vec<u8>, Option<String>)> {
+    let interactor = &mut interaction.process;
+
+    let interactor_stdin = interactor
+        .stdin
+        .take()
+        .context("Failed to take interactor's stdin")?;
+    let interactor_stdout = interactor
+        .stdout
+        .take()
+        .context("Failed to take interactor's stdout")?;
+    let mut interactor_stderr = interactor
+        .stderr
+        .take()
+        .context("Failed to take interactor's stderr")?;
+
+    let (sent, _, stderr, interactor_stderr) = tokio::join!(
+        forward(stdout, interactor_stdin, args.output_limit),
+        forward(interactor_stdout, stdin, u64::MAX),
+        read_stderr(&mut stderr, args.output_limit),
+        read_stderr(&mut interactor_stderr, args.output_limit),
+    );
+
+    let status = interactor.wait().await?;
+    if status.success() {
+        return Ok((sent?, stderr?, None));
+    }
+
+    // testlib interactors report the verdict on stderr, but may write it to the output file
+    let interactor_stderr = interactor_stderr?;
+    let message = match interactor_stderr.trim() {
+        [] => fs::read(&interaction.output).await.unwrap_or_default(),
+        x => x.to_vec(),
+    };
+
+    Ok((
+        sent?,
+        stderr?,
+        Some(format!(
+            "Rejected by the interactor, which {}:\n{}",
+            describe_exit_status(&status),
+            String::from_utf8_lossy(message.trim())
+        )),
+    ))
+}
+
+/// A path to the data for external programs like the checker: the file itself if it's a plain
+/// file, otherwise a file named `name` in `dir` which the data is written to. No data (or a file
+/// which doesn't exist) results in an empty file.
+async fn data_path(data: Option<&TestData>, dir: &Path, name: &str) -> Result<PathBuf> {
+    if let Some(path) = data.and_then(TestData::plain_path).filter(|x| x.exists()) {
+        return Ok(path.to_path_buf());
     }
 
-    async fn is_correct(&self, actual: Vec<u8>) -> Result<bool> {
-        let expected = self.get_output().await?;
+    let contents = match data {
+        Some(data) if !data.is_missing() => data.read().await?,
+        _ => vec![],
+    };
+
+    let path = dir.join(name);
+    fs::write(&path, contents).await?;
+    Ok(path)
+}
+
+/// How much of the program's stderr is shown in the failure log
+const STDERR_LOG_LIMIT: usize = 2000;
 
-        let actual = actual.as_slice().trim();
-        let expected = expected.as_slice().trim();
+/// Formats the program's stderr for the failure log, truncating it if it's long
+fn stderr_note(stderr: &[u8]) -> String {
+    let stderr = stderr.trim();
+    if stderr.is_empty() {
+        return String::new();
+    }
 
-        Ok(actual == expected)
+    if stderr.len() > STDERR_LOG_LIMIT {
+        format!(
+            "\nStderr (truncated):\n{}…",
+            String::from_utf8_lossy(&stderr[..STDERR_LOG_LIMIT])
+        )
+    } else {
+        format!("\nStderr:\n{}", String::from_utf8_lossy(stderr))
     }
 }
 
+/// Runs the output validator on the program's output.
+/// Returns the validator's message if it rejected the output (exited with a non-zero code).
+async fn validate_output(validator: &str, output: &[u8]) -> Result<Option<String>> {
+    let res = run_with_input(parse_command(validator)?, output).await?;
+
+    if res.status.success() {
+        return Ok(None);
+    }
+
+    let message = if res.stderr.trim().is_empty() {
+        &res.stdout
+    } else {
+        &res.stderr
+    };
+
+    Ok(Some(format!(
+        "Rejected by the output validator, which {}:\n{}",
+        describe_exit_status(&res.status),
+        String::from_utf8_lossy(message.trim())
+    )))
+}
+
+/// Limits the address space of the spawned process to the given amount of bytes
+#[cfg(unix)]
+fn limit_memory(command: &mut Command, bytes: u64) {
+    let limit = libc::rlimit {
+        rlim_cur: bytes as libc::rlim_t,
+        rlim_max: bytes as libc::rlim_t,
+    };
+
+    // SAFETY: setrlimit is async-signal-safe and the closure doesn't allocate
+    unsafe {
+        command.pre_exec(move || {
+            if libc::setrlimit(libc::RLIMIT_AS, &limit) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+
+            Ok(())
+        });
+    }
+}
+
+/// Guesses whether a crashed program ran out of memory: when an allocation fails under the
+/// address space limit, programs usually abort with a message like C++'s `std::bad_alloc`,
+/// while the OOM killer uses SIGKILL.
+fn looks_like_out_of_memory(status: &ExitStatus, stderr: &[u8]) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        if status.signal() == Some(libc::SIGKILL) {
+            return true;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = status;
+
+    let stderr = String::from_utf8_lossy(stderr).to_lowercase();
+    [
+        "bad_alloc",
+        "memory allocation",
+        "out of memory",
+        "cannot allocate memory",
+        "memoryerror",
+    ]
+    .iter()
+    .any(|x| stderr.contains(x))
+}
+
+/// Waits for the program to exit. On Unix, it's reaped with `wait4`, which also reports its
+/// peak resident set size in bytes, including allocations right before exiting which sampling
+/// would miss.
+///
+/// Linux counts the memory of the process which started the program into it too, so it's only
+/// returned if it's more than `own_peak_memory` (the peak of competitest after starting it).
+/// Otherwise the program's own peak is unknown and `None` is returned.
+async fn wait_for_exit(
+    child: &mut Child,
+    own_peak_memory: Option<u64>,
+) -> Result<(ExitStatus, Option<u64>)> {
+    #[cfg(unix)]
+    {
+        if let Some(pid) = child.id() {
+            // wait4 blocks until the program exits. If the test times out in the meantime,
+            // the program is killed on drop, which lets this return.
+            let (status, max_rss) = tokio::task::spawn_blocking(move || wait4(pid)).await??;
+            let max_rss = Some(max_rss).filter(|&x| own_peak_memory.is_some_and(|own| x > own));
+
+            return Ok((status, max_rss));
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = own_peak_memory;
+
+    Ok((child.wait().await?, None))
+}
+
+/// Reaps the given child process, returning its exit status and peak resident set size in bytes
+#[cfg(unix)]
+fn wait4(pid: u32) -> std::io::Result<(ExitStatus, u64)> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let mut status = 0;
+    // SAFETY: rusage is plain old data, for which all zeroes is a valid value
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+
+    loop {
+        // SAFETY: both pointers are valid for the duration of the call
+        if unsafe { libc::wait4(pid as libc::pid_t, &mut status, 0, &mut rusage) } != -1 {
+            break;
+        }
+
+        let e = std::io::Error::last_os_error();
+        if e.kind() != std::io::ErrorKind::Interrupted {
+            return Err(e);
+        }
+    }
+
+    Ok((ExitStatus::from_raw(status), max_rss_bytes(&rusage)))
+}
+
+/// The peak resident set size of competitest itself in bytes, only known on Unix
+fn own_peak_memory() -> Option<u64> {
+    #[cfg(unix)]
+    {
+        // SAFETY: rusage is plain old data, for which all zeroes is a valid value
+        let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+
+        // SAFETY: the pointer is valid for the duration of the call
+        if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut rusage) } != 0 {
+            return None;
+        }
+
+        Some(max_rss_bytes(&rusage))
+    }
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
+/// Converts `ru_maxrss` to bytes. It's in kilobytes, except on macOS, where it's in bytes.
+#[cfg(unix)]
+fn max_rss_bytes(rusage: &libc::rusage) -> u64 {
+    let max_rss = rusage.ru_maxrss as u64;
+
+    if cfg!(target_os = "macos") {
+        max_rss
+    } else {
+        max_rss * 1024
+    }
+}
+
+/// How often the memory usage of a running program is sampled
+const MEMORY_SAMPLE_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Reads the peak resident set size of a running process in bytes. Only supported on Linux,
+/// where it's read from `/proc/<pid>/status`. It's a fallback for when `wait4` can't be used.
+fn read_peak_memory(pid: Option<u32>) -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        let status = std::fs::read_to_string(format!("/proc/{}/status", pid?)).ok()?;
+        let kilobytes = status
+            .lines()
+            .find_map(|x| x.strip_prefix("VmHWM:"))?
+            .trim()
+            .strip_suffix("kB")?
+            .trim()
+            .parse::<u64>()
+            .ok()?;
+
+        Some(kilobytes * 1024)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = pid;
+        None
+    }
+}
+
+/// Formats the time and peak memory usage of a test, e.g. "0.12 s, 3.4 MB"
+fn describe_usage(time: Duration, peak_memory: Option<u64>) -> String {
+    match peak_memory {
+        Some(bytes) => format!(
+            "{:.2} s, {:.1} MB",
+            time.as_secs_f64(),
+            bytes as f64 / 1024.0 / 1024.0
+        ),
+        None => format!("{:.2} s", time.as_secs_f64()),
+    }
+}
+
+/// Describes how a process ended, e.g. "exited with code 1"
+pub fn describe_exit_status(status: &ExitStatus) -> String {
+    if let Some(code) = status.code() {
+        return format!("exited with code {}", code);
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Some(signal) = status.signal() {
+            return format!("was killed by signal {}", signal);
+        }
+    }
+
+    format!("ended with {}", status)
+}
+
+/// The result of running the program once
+struct Execution {
+    output: Output,
+    /// From starting the program until it exited
+    time: Duration,
+    /// The program was killed for printing more than `--output-limit` bytes
+    output_limit_exceeded: bool,
+    /// In bytes, see `read_peak_memory`
+    peak_memory: Option<u64>,
+    /// Why the interactor rejected the interaction, see `interact`
+    interactor_rejection: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 pub enum TestTimeoutResult {
     TimedOut(
         /// The name of the test
         String,
+        /// The time limit the test exceeded
+        Duration,
     ),
 
     Finished(TestResult),
 }
 
+impl TestTimeoutResult {
+    pub fn name(&self) -> &str {
+        match self {
+            TestTimeoutResult::TimedOut(name, _) => name,
+            TestTimeoutResult::Finished(res) => &res.name,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TestResult {
     pub name: String,
 
+    /// With `--repeat`, the mean time of all repetitions
     pub time: Duration,
+    /// The time of each repetition
+    pub timings: Vec<Duration>,
     pub correct: bool,
+    /// The program exited with a non-zero code or was killed by a signal
+    pub runtime_error: bool,
+    /// The program crashed because of running out of memory under `--memory-limit`
+    pub memory_limit_exceeded: bool,
+    /// The test passed, but took more than `--warn-ratio` of its time limit
+    pub slow_pass: bool,
+    /// The test passed, but only after retrying a timeout (see `--retry-timeouts`)
+    pub passed_after_retry: bool,
+    /// Peak resident set size in bytes (the highest one for `--repeat`), `None` if unknown
+    pub peak_memory: Option<u64>,
 
-    pub stdin: Vec<u8>,
+    /// `None` if there was no expected output to compare against
+    pub expected: Option<Vec<u8>>,
+    /// For failed tests, how similar the output was to the expected one (from 0 to 1)
+    pub similarity: Option<f64>,
+    /// The program's stdout and exit status. Its stderr is moved to `stderr`.
     pub output: Output,
+    pub stderr: Vec<u8>,
 }
 
-pub fn get_tests(args: &crate::Args) -> Result<Vec<Test>> {
-    let task = args.task.clone();
-    let task_in_pattern = args.in_pattern.replace("{task}", &task);
+impl TestResult {
+    /// The median time of the repetitions (see `--repeat`), which unlike the mean isn't skewed
+    /// by a single slow run
+    pub fn median_time(&self) -> Duration {
+        let mut timings = self.timings.clone();
+        timings.sort();
+        timings.get(timings.len() / 2).copied().unwrap_or(self.time)
+    }
+}
+
+/// Reads a tags file, where each line is a test name followed by its tags, separated by whitespace.
+/// Empty lines and lines starting with `#` are ignored. A missing file means there are no tags.
+fn read_tags(path: &Path) -> Result<HashMap<String, Vec<String>>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(x) => x,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => Err(e)?,
+    };
+
+    let mut tags: HashMap<String, Vec<String>> = HashMap::new();
 
-    Ok(glob(&task_in_pattern.replace("{test}", "*"))?
-        .map_ok(|x| -> Result<Test> {
-            let path_str = x.to_string_lossy();
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut words = line.split_whitespace().map(str::to_string);
+        if let Some(name) = words.next() {
+            tags.entry(name).or_default().extend(words);
+        }
+    }
+
+    Ok(tags)
+}
+
+/// Reads a per-test timeout (in the same format as `--timeout`) from the given file. If it's
+/// missing or malformed, the global timeout is used.
+fn read_timeout(path: &Path) -> Option<Duration> {
+    let contents = std::fs::read_to_string(path).ok()?;
+
+    match crate::args::parse_duration(contents.trim()) {
+        Ok(timeout) => Some(timeout),
+        Err(e) => {
+            warn!(
+                "Malformed timeout in {} ({}), using the global timeout.",
+                path.display(),
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Whether the file is gzip-compressed, judging by its extension
+fn is_gzip(path: &Path) -> bool {
+    path.extension().is_some_and(|x| x == "gz")
+}
+
+/// The path of the file without the `.gz` extension of a compressed file
+fn without_gz(path: &Path) -> PathBuf {
+    if is_gzip(path) {
+        path.with_extension("")
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Finds the input files matching an input pattern, returning the name, input and expected
+/// output path of each test. Compressed `.gz` versions of the input and output files are
+/// found too, but uncompressed files take precedence.
+fn glob_pattern(
+    task: &str,
+    in_pattern: &str,
+    out_pattern: &str,
+) -> Result<Vec<(String, PathBuf, PathBuf)>> {
+    let task_in_pattern = in_pattern.replace("{task}", task);
+    let glob_pattern = task_in_pattern.replace("{test}", "*");
+
+    let compressed = if is_gzip(Path::new(&task_in_pattern)) {
+        vec![]
+    } else {
+        glob(&format!("{}.gz", glob_pattern))?
+            .filter_ok(|x| !without_gz(x).exists())
+            .collect()
+    };
+
+    glob(&glob_pattern)?
+        .chain(compressed)
+        .map_ok(|x| -> Result<(String, PathBuf, PathBuf)> {
+            let path_str = if is_gzip(Path::new(&task_in_pattern)) {
+                x.to_string_lossy().into_owned()
+            } else {
+                without_gz(&x).to_string_lossy().into_owned()
+            };
 
             let test_pos = task_in_pattern
                 .find("{test}")
@@ -146,16 +1414,211 @@ pub fn get_tests(args: &crate::Args) -> Result<Vec<Test>> {
                 ..(path_str.len() - (task_in_pattern.len() - (test_pos + "{test}".len())))]
                 .to_string();
 
-            Ok(Test {
-                name: test_name.clone(),
-                in_file: x,
-                out_file: PathBuf::from(
-                    args.out_pattern
-                        .replace("{task}", &task)
-                        .replace("{test}", &test_name),
-                ),
-            })
+            let mut out_file = PathBuf::from(
+                out_pattern
+                    .replace("{task}", task)
+                    .replace("{test}", &test_name),
+            );
+
+            let compressed_out_file = PathBuf::from(format!("{}.gz", out_file.display()));
+            if !is_gzip(&out_file) && !out_file.exists() && compressed_out_file.exists() {
+                out_file = compressed_out_file;
+            }
+
+            Ok((test_name, x, out_file))
         })
         .flatten()
-        .collect::<Result<Vec<Test>>>()?)
+        .collect()
+}
+
+/// Labels used to tell apart tests with the same name from different input patterns: the name
+/// of the directory of each pattern if they're all different, its position otherwise
+fn pattern_labels(patterns: &[String]) -> Vec<String> {
+    let dirs = patterns
+        .iter()
+        .map(|x| {
+            Path::new(x)
+                .parent()
+                .and_then(|x| x.file_name())
+                .map(|x| x.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        })
+        .collect_vec();
+
+    if dirs.iter().all(|x| !x.is_empty()) && dirs.iter().all_unique() {
+        dirs
+    } else {
+        (1..=patterns.len()).map(|x| x.to_string()).collect()
+    }
+}
+
+/// Reads a per-test expected exit code from the given file. If it's missing, `--expected-exit`
+/// (if any) is used.
+fn read_exit_code(path: &Path) -> Option<i32> {
+    let contents = std::fs::read_to_string(path).ok()?;
+
+    match contents.trim().parse() {
+        Ok(code) => Some(code),
+        Err(_) => {
+            warn!(
+                "Malformed exit code in {}, using the global one.",
+                path.display()
+            );
+            None
+        }
+    }
+}
+
+fn get_file_tests(args: &crate::Args, tags: &HashMap<String, Vec<String>>) -> Result<Vec<Test>> {
+    if args.in_pattern.len() != args.out_pattern.len() {
+        bail!(
+            "Got {} input patterns, but {} output patterns",
+            args.in_pattern.len(),
+            args.out_pattern.len()
+        );
+    }
+
+    // the patterns may overlap, so each input file is only used once
+    let mut seen_inputs = HashSet::new();
+    let mut groups = vec![];
+
+    for (in_pattern, out_pattern) in args.in_pattern.iter().zip(&args.out_pattern) {
+        let mut tests = glob_pattern(&args.task, in_pattern, out_pattern)?;
+        tests.retain(|(_, input, _)| {
+            seen_inputs.insert(std::fs::canonicalize(input).unwrap_or_else(|_| input.clone()))
+        });
+        groups.push(tests);
+    }
+
+    let name_counts = groups.iter().flatten().map(|x| x.0.clone()).counts();
+    let name_counts = &name_counts;
+    let labels = pattern_labels(&args.in_pattern);
+
+    Ok(groups
+        .into_iter()
+        .zip(labels)
+        .flat_map(|(tests, label)| {
+            tests.into_iter().map(move |(name, input, expected)| {
+                let name = if name_counts[&name] > 1 {
+                    format!("{}-{}", label, name)
+                } else {
+                    name
+                };
+
+                Test {
+                    tags: tags.get(&name).cloned().unwrap_or_default(),
+                    timeout: read_timeout(&without_gz(&input).with_extension("timeout")),
+                    exit_code: read_exit_code(&without_gz(&input).with_extension("exit")),
+                    name,
+                    input: TestData::File(input),
+                    expected: Some(TestData::File(expected)),
+                }
+            })
+        })
+        .collect())
+}
+
+fn get_template_tests(
+    args: &crate::Args,
+    template: &Path,
+    tags: &HashMap<String, Vec<String>>,
+) -> Result<Vec<Test>> {
+    let params = args
+        .template_params
+        .as_ref()
+        .context("--template requires --template-params")?;
+
+    Ok(
+        generate_tests(template, params, args.template_out.as_deref())?
+            .into_iter()
+            .map(|x| Test {
+                tags: tags.get(&x.name).cloned().unwrap_or_default(),
+                timeout: None,
+                exit_code: None,
+                name: x.name,
+                input: TestData::Memory(x.input),
+                expected: x.expected.map(TestData::Memory),
+            })
+            .collect(),
+    )
+}
+
+pub fn get_tests(args: &crate::Args) -> Result<Vec<Test>> {
+    let tags = read_tags(Path::new(&args.tags_file.replace("{task}", &args.task)))?;
+
+    let mut tests = match &args.template {
+        Some(template) => get_template_tests(args, template, &tags)?,
+        None => get_file_tests(args, &tags)?,
+    };
+
+    tests.sort_by(|a, b| natural_cmp(&a.name, &b.name));
+
+    if !args.tag.is_empty() {
+        tests.retain(|x| x.tags.iter().any(|tag| args.tag.contains(tag)));
+    }
+
+    if let Some(only) = &args.only {
+        tests.retain(|x| &x.name == only);
+
+        if tests.is_empty() {
+            bail!("No test named {} found", only);
+        }
+    }
+
+    if let Some(filter) = &args.filter {
+        let total = tests.len();
+        tests.retain(|x| filter.matches(&x.name));
+
+        info!(
+            "Skipping {} tests not matching the filter {}.",
+            total - tests.len(),
+            filter
+        );
+    }
+
+    // outputs aren't needed yet when accepting them or only checking exit codes, a checker or
+    // interactor may not need them, and --dry-run warns about them itself
+    if !args.accept
+        && !args.dry_run
+        && args.expect_exit.is_none()
+        && args.checker.is_none()
+        && args.interactor.is_none()
+    {
+        check_missing_outputs(&mut tests, args.skip_missing_outputs)?;
+    }
+
+    Ok(tests)
+}
+
+/// Fails listing all tests whose expected output file doesn't exist, or skips them if `skip`
+/// is set
+fn check_missing_outputs(tests: &mut Vec<Test>, skip: bool) -> Result<()> {
+    let missing = tests
+        .iter()
+        .filter_map(|x| match &x.expected {
+            Some(expected) if expected.is_missing() => Some(format!("{} ({})", x.name, expected)),
+            _ => None,
+        })
+        .collect_vec();
+
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    if !skip {
+        bail!(
+            "The expected output of {} tests doesn't exist (pass --skip-missing-outputs to skip them):\n{}",
+            missing.len(),
+            missing.join("\n")
+        );
+    }
+
+    warn!(
+        "Skipping {} tests whose expected output doesn't exist:\n{}",
+        missing.len(),
+        missing.join("\n")
+    );
+    tests.retain(|x| !x.expected.as_ref().is_some_and(TestData::is_missing));
+
+    Ok(())
 }
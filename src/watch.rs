@@ -0,0 +1,98 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use color_eyre::{eyre::eyre, Result};
+use log::warn;
+use notify::{
+    event::{AccessKind, AccessMode},
+    Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher as _,
+};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+/// How long to wait for more events after a change, so e.g. a compiler writing a file in
+/// multiple steps only triggers one run
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Watches files and directories for changes
+pub struct Watcher {
+    _watcher: RecommendedWatcher,
+    events: UnboundedReceiver<()>,
+}
+
+impl Watcher {
+    /// Directories are watched recursively. Files are watched through their parent directory,
+    /// so they are still tracked after being replaced (e.g. by a compiler).
+    pub fn new(paths: &[PathBuf]) -> Result<Self> {
+        let mut files = vec![];
+        let mut dirs = vec![];
+
+        for path in paths {
+            match path.canonicalize() {
+                Ok(path) if path.is_dir() => dirs.push(path),
+                Ok(path) => files.push(path),
+                Err(e) => warn!("Can't watch {}: {}", path.display(), e),
+            }
+        }
+
+        if files.is_empty() && dirs.is_empty() {
+            return Err(eyre!("Nothing to watch, see --watch-path"));
+        }
+
+        let (tx, events) = mpsc::unbounded_channel();
+
+        let watched_files = files.clone();
+        let watched_dirs = dirs.clone();
+
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let Ok(event) = event else { return };
+
+            // opening and reading files (e.g. running the program) isn't a change
+            if matches!(event.kind, EventKind::Access(kind) if kind != AccessKind::Close(AccessMode::Write))
+            {
+                return;
+            }
+
+            let relevant = event.paths.iter().any(|path| {
+                watched_files.iter().any(|x| x == path)
+                    || watched_dirs.iter().any(|x| path.starts_with(x))
+            });
+
+            if relevant {
+                let _ = tx.send(());
+            }
+        })?;
+
+        for file in &files {
+            watcher.watch(
+                file.parent().unwrap_or(Path::new("/")),
+                RecursiveMode::NonRecursive,
+            )?;
+        }
+        for dir in &dirs {
+            watcher.watch(dir, RecursiveMode::Recursive)?;
+        }
+
+        Ok(Self {
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    /// Discards the changes which happened so far, e.g. during a run
+    pub fn drain(&mut self) {
+        while self.events.try_recv().is_ok() {}
+    }
+
+    /// Waits for the next change
+    pub async fn changed(&mut self) {
+        if self.events.recv().await.is_none() {
+            // the watcher is gone, so there won't be any more changes
+            std::future::pending::<()>().await;
+        }
+
+        tokio::time::sleep(DEBOUNCE).await;
+        self.drain();
+    }
+}